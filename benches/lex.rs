@@ -0,0 +1,115 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use lemon_pie::lexer::*;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Builds a large program out of a fixed vocabulary, driven by a xorshift
+/// generator so every run lexes the same bytes.
+fn synthetic_source(words: usize, mut seed: u64) -> String {
+    const VOCAB: &[&str] = &[
+        "fn", "let", "var", "if", "else", "match", "counter", "value_2",
+        "lemon", "pie", "x", "42", "3.14", "+", "-", "==", "..=", "(", ")",
+        "{", "}", "[", "]", ",", ".",
+    ];
+    let mut ret = String::new();
+    for _ in 0..words {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        ret.push_str(VOCAB[(seed % VOCAB.len() as u64) as usize]);
+        ret.push(if seed.is_multiple_of(11) { '\n' } else { ' ' });
+    }
+    ret
+}
+
+fn allocations_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let ret = f();
+    (ret, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+fn lex_owned_vs_borrowed(c: &mut Criterion) {
+    let source = synthetic_source(200_000, 0x5eed);
+
+    let (owned, owned_allocs) = allocations_during(|| Lexer::new(&source).lex().unwrap());
+    let (_, borrowed_allocs) = allocations_during(|| Lexer::new(&source).lex_borrowed().unwrap());
+    println!("{} tokens: owned {owned_allocs} allocations, borrowed {borrowed_allocs} allocations", owned.len());
+
+    let mut group = c.benchmark_group("lex");
+    group.bench_function("owned", |b| b.iter(|| Lexer::new(&source).lex().unwrap()));
+    group.bench_function("borrowed", |b| b.iter(|| Lexer::new(&source).lex_borrowed().unwrap()));
+    group.finish();
+}
+
+/// Lexing, then finding every use of one identifier, which is where
+/// interning pays off: each comparison is a `u32` instead of a string.
+fn lex_plain_vs_interned(c: &mut Criterion) {
+    let source = synthetic_source(200_000, 0x5eed);
+    let uses_of_counter = |tokens: &[Token]| {
+        tokens.iter().filter(|token| matches!(token, Token::Ident(name) if name == "counter")).count()
+    };
+    let interned_uses_of_counter = |(tokens, interner): &(Vec<Token>, lemon_pie::frontend::interner::Interner)| {
+        let counter = interner.get("counter");
+        tokens.iter().filter(|token| matches!(token, Token::InternedIdent(symbol) if Some(*symbol) == counter)).count()
+    };
+
+    let (interned, interned_allocs) = allocations_during(|| Lexer::new(&source).lex_interned().unwrap());
+    assert_eq!(uses_of_counter(&Lexer::new(&source).lex().unwrap()), interned_uses_of_counter(&interned));
+    println!("{} distinct identifiers, interned lexing makes {interned_allocs} allocations", interned.1.len());
+
+    let tokens = Lexer::new(&source).lex().unwrap();
+    let mut group = c.benchmark_group("identifiers");
+    group.bench_function("lex", |b| b.iter(|| Lexer::new(&source).lex().unwrap()));
+    group.bench_function("lex_interned", |b| b.iter(|| Lexer::new(&source).lex_interned().unwrap()));
+    group.bench_function("find_plain", |b| b.iter(|| uses_of_counter(&tokens)));
+    group.bench_function("find_interned", |b| b.iter(|| interned_uses_of_counter(&interned)));
+    group.finish();
+}
+
+/// Re-lexing a file the size of one being edited, the way an editor does
+/// on every keystroke.
+fn relex_new_vs_reset(c: &mut Criterion) {
+    let source = synthetic_source(2_000, 0x5eed);
+
+    let (_, new_allocs) = allocations_during(|| Lexer::new(&source).lex().unwrap());
+    let mut lexer = Lexer::new(&source);
+    let mut buf = Vec::new();
+    lexer.lex_into(&mut buf).unwrap();
+    let (_, reset_allocs) = allocations_during(|| {
+        lexer.reset(&source);
+        lexer.lex_into(&mut buf).unwrap();
+    });
+    println!("{} tokens: new {new_allocs} allocations, reset {reset_allocs} allocations", buf.len());
+
+    let mut group = c.benchmark_group("relex");
+    group.bench_function("new", |b| b.iter(|| Lexer::new(&source).lex().unwrap()));
+    group.bench_function("reset", |b| {
+        b.iter(|| {
+            lexer.reset(&source);
+            lexer.lex_into(&mut buf).unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lex_owned_vs_borrowed, lex_plain_vs_interned, relex_new_vs_reset);
+criterion_main!(benches);
@@ -1,26 +1,1006 @@
+use core::{error::Error, fmt::{Debug, Display}};
+use alloc::{boxed::Box, format, string::{String, ToString}, vec, vec::Vec};
+
 use super::lexer::*;
+use super::error::FrontendError;
+use super::token_stream::{TokenStream, UnexpectedToken};
 
-pub struct ASTParser<'a> {
-    reader: TokenStreamReader<'a>,
+/// Lexes and parses a whole program, recovering from bad tokens and bad
+/// items so that every item that could be built is returned alongside all
+/// the errors found. Doc comments are skipped.
+pub fn parse_program(source: &str) -> (Vec<Item>, Vec<FrontendError>) {
+    parse_program_with(source, false)
 }
-
-struct TokenStreamReader<'a> {
-    src: &'a Vec<Token>,
+/// Like `parse_program`, with the lexer's `strict_ops` lints turned on or
+/// off. Any lints come first in the errors. Other lexer options change the
+/// tokens the parser sees, so they can't be set here.
+pub fn parse_program_with(source: &str, strict_ops: bool) -> (Vec<Item>, Vec<FrontendError>) {
+    let mut lexer = Lexer::new(source).strict_ops(strict_ops);
+    // The parser still gets the tokens around a lexing error, so the items
+    // away from it are kept.
+    let (tokens, lex_errors) = lexer.lex_recovering();
+    let mut diagnostics: Vec<FrontendError> = lexer.take_lints().into_iter().map(FrontendError::from).collect();
+    diagnostics.extend(lex_errors.into_iter().map(FrontendError::from));
+    let (tokens, offsets): (Vec<Token>, Vec<usize>) =
+        tokens.into_iter().map(|(token, span)| (token, span.start)).unzip();
+    let (items, errors) = ASTParser::with_offsets(&tokens, &offsets).parse_recovering();
+    diagnostics.extend(errors.into_iter().map(FrontendError::from));
+    (items, diagnostics)
 }
-impl<'a> TokenStreamReader<'a> {
 
+pub struct ASTParser<'a> {
+    reader: TokenStream<'a>,
+    /// Cleared while parsing the condition of an `if`, where `cond {` has to
+    /// open the body rather than a struct literal named `cond`. Anything
+    /// nested inside parens, brackets or a block turns it back on.
+    struct_literals: bool,
+    /// How many expressions, types and patterns are open around the one
+    /// being parsed.
+    depth: usize,
 }
 
+/// How deeply expressions, types and patterns may nest, so that something
+/// like a hundred thousand `(` is an error rather than a stack overflow.
+const MAX_NESTING: usize = 256;
+
 impl<'a> ASTParser<'a> {
-    pub fn parse() -> Result<ASTNode, ASTParsingError> {
-        todo!()
+    pub fn new(tokens: &'a [Token<'a>]) -> Self {
+        Self::with_offsets(tokens, &[])
+    }
+    /// A parser whose errors point at `offsets[i]` for the `i`th token.
+    pub fn with_offsets(tokens: &'a [Token<'a>], offsets: &'a [usize]) -> Self {
+        Self {
+            reader: TokenStream::with_offsets(tokens, offsets),
+            struct_literals: true,
+            depth: 0,
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Item>, ASTParsingError> {
+        let mut ret = Vec::new();
+        while !self.at_eof() {
+            ret.push(self.parse_item()?);
+        }
+        Ok(ret)
+    }
+    /// Like `parse`, but after a bad item skips ahead to the next token that
+    /// can start an item and carries on.
+    pub fn parse_recovering(&mut self) -> (Vec<Item>, Vec<ASTParsingError>) {
+        let mut items = Vec::new();
+        let mut errors = Vec::new();
+        while !self.at_eof() {
+            match self.parse_item() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    errors.push(err);
+                    while *self.reader.peek() != Token::EOF && !is_item_start(self.reader.peek()) {
+                        self.reader.next();
+                    }
+                }
+            }
+        }
+        (items, errors)
+    }
+    pub fn parse_item(&mut self) -> Result<Item, ASTParsingError> {
+        self.skip_doc_comments();
+        match self.reader.next() {
+            Token::Const => {
+                let (name, ty, value) = self.parse_global_rest("Const")?;
+                Ok(Item::Const { name, ty, value })
+            }
+            Token::Static => {
+                let (name, ty, value) = self.parse_global_rest("Static")?;
+                Ok(Item::Static { name, ty, value })
+            }
+            Token::Enum => self.parse_enum_rest(),
+            Token::Fn => self.parse_fn_rest(),
+            other => Err(self.error_at_last(format!("Expected Item, Found {:?}", other))),
+        }
+    }
+    /// Parses a function after `fn`. The body is either a block, or `=`
+    /// followed by a single expression and a `;`.
+    fn parse_fn_rest(&mut self) -> Result<Item, ASTParsingError> {
+        let name = self.parse_ident()?;
+        self.reader.expect(&Token::Paren('('))?;
+        let params = self.parse_params(&Token::Paren(')'))?;
+        let body = match self.reader.next() {
+            Token::Paren('{') => FnBody::Block(self.parse_block_rest()?),
+            Token::Op(Op::Eq) => {
+                let expr = self.parse_expr()?;
+                self.reader.expect(&Token::Op(Op::Semi))?;
+                FnBody::Expr(expr)
+            }
+            other => {
+                let message = format!("Expected '{{' Or '=' After The Signature Of '{name}', Found {:?}", other);
+                return Err(self.error_at_last(message));
+            }
+        };
+        Ok(Item::Fn { name, params, body })
+    }
+    fn parse_enum_rest(&mut self) -> Result<Item, ASTParsingError> {
+        let name = self.parse_ident()?;
+        self.reader.expect(&Token::Paren('{'))?;
+        let variants = self.parse_comma_list(&Token::Paren('}'), Self::parse_variant)?;
+        Ok(Item::Enum { name, variants })
+    }
+    /// Parses `Name`, `Name(Type, ..)` or `Name { field: Type, .. }`.
+    fn parse_variant(&mut self) -> Result<Variant, ASTParsingError> {
+        let name = self.parse_ident()?;
+        let fields = if self.reader.eat(&Token::Paren('(')) {
+            VariantFields::Tuple(self.parse_comma_list(&Token::Paren(')'), Self::parse_type)?)
+        } else if self.reader.eat(&Token::Paren('{')) {
+            VariantFields::Struct(self.parse_comma_list(&Token::Paren('}'), |this| {
+                let field = this.parse_ident()?;
+                this.reader.expect(&Token::Op(Op::Colon))?;
+                Ok((field, this.parse_type()?))
+            })?)
+        } else {
+            VariantFields::Unit
+        };
+        Ok(Variant { name, fields })
+    }
+    /// Parses `NAME: Type = expr;` after `const` or `static`, both of which
+    /// require the type and the initializer.
+    fn parse_global_rest(&mut self, what: &str) -> Result<(String, Type, Expr), ASTParsingError> {
+        let name = self.parse_ident()?;
+        if !self.reader.eat(&Token::Op(Op::Colon)) {
+            return Err(self.error(format!("{what} Item '{name}' Is Missing Its Type")));
+        }
+        let ty = self.parse_type()?;
+        if !self.reader.eat(&Token::Op(Op::Eq)) {
+            return Err(self.error(format!("{what} Item '{name}' Is Missing Its Value")));
+        }
+        let value = self.parse_expr()?;
+        self.reader.expect(&Token::Op(Op::Semi))?;
+        Ok((name, ty, value))
+    }
+
+    pub fn parse_expr(&mut self) -> Result<Expr, ASTParsingError> {
+        self.parse_binary(0)
+    }
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ASTParsingError> {
+        let mut lhs = self.parse_cast()?;
+        while let Token::Op(op) = self.reader.peek() {
+            let precedence = match binary_precedence(*op) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.reader.next();
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = Expr::Binary { op: *op, lhs: Box::new(lhs), rhs: Box::new(rhs) };
+        }
+        Ok(lhs)
+    }
+    /// `as` binds looser than unary operators but tighter than any binary
+    /// one, so `-x as T` casts `-x`.
+    fn parse_cast(&mut self) -> Result<Expr, ASTParsingError> {
+        let mut expr = self.parse_unary()?;
+        while self.reader.eat(&Token::As) {
+            expr = Expr::Cast { expr: Box::new(expr), ty: self.parse_type()? };
+        }
+        Ok(expr)
+    }
+    /// Every nested expression is parsed through here, so this is where
+    /// the nesting depth is counted.
+    fn parse_unary(&mut self) -> Result<Expr, ASTParsingError> {
+        self.nested(|this| match this.reader.peek() {
+            Token::Op(op @ (Op::Minus | Op::Bang | Op::Tilde)) => {
+                this.reader.next();
+                Ok(Expr::Unary { op: *op, expr: Box::new(this.parse_unary()?) })
+            }
+            _ => this.parse_postfix(),
+        })
+    }
+    /// Field accesses, calls, indexing and `?` all bind to whatever is on
+    /// their left, so they are folded in a single loop in source order.
+    fn parse_postfix(&mut self) -> Result<Expr, ASTParsingError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            expr = match self.reader.peek() {
+                Token::Op(Op::Dot) => {
+                    self.reader.next();
+                    Expr::Field { expr: Box::new(expr), name: self.parse_field_name()? }
+                }
+                Token::Paren('(') => {
+                    self.reader.next();
+                    let args = self.with_struct_literals(true, |this| {
+                        this.parse_comma_list(&Token::Paren(')'), Self::parse_expr)
+                    })?;
+                    Expr::Call { callee: Box::new(expr), args }
+                }
+                Token::Paren('[') => {
+                    self.reader.next();
+                    let index = self.with_struct_literals(true, Self::parse_expr)?;
+                    self.reader.expect(&Token::Paren(']'))?;
+                    Expr::Index { expr: Box::new(expr), index: Box::new(index) }
+                }
+                Token::Op(Op::Question) => {
+                    self.reader.next();
+                    Expr::Try(Box::new(expr))
+                }
+                _ => break,
+            };
+        }
+        Ok(expr)
+    }
+    fn parse_primary(&mut self) -> Result<Expr, ASTParsingError> {
+        match self.reader.next() {
+            Token::Ident(first) if *self.reader.peek() == Token::Op(Op::ColonColon) => {
+                Ok(Expr::Path(self.parse_path_rest(first.to_string())?))
+            }
+            Token::Ident(name) if self.struct_literals && self.reader.eat(&Token::Paren('{')) => {
+                self.parse_struct_literal_rest(name.to_string())
+            }
+            Token::Ident(name) => Ok(Expr::Ident(name.to_string())),
+            token @ (Token::IntLit { .. } | Token::FloatLit { .. }) => Ok(number_literal(token)),
+            Token::CharLit(ch) => Ok(Expr::CharLit(*ch)),
+            Token::BoolLit(b) => Ok(Expr::BoolLit(*b)),
+            Token::StrLit(s) => Ok(Expr::StrLit(s.clone())),
+            Token::ByteLit(ch) => Ok(Expr::ByteLit(*ch)),
+            Token::ByteStrLit(s) => Ok(Expr::ByteStrLit(s.clone())),
+            Token::Undefined => Ok(Expr::Undefined),
+            Token::Paren('(') => {
+                let expr = self.with_struct_literals(true, Self::parse_expr)?;
+                self.reader.expect(&Token::Paren(')'))?;
+                Ok(expr)
+            }
+            Token::Paren('{') => Ok(Expr::Block(self.parse_block_rest()?)),
+            Token::If => self.parse_if_rest(),
+            Token::While => self.parse_while_rest(),
+            Token::Match => self.parse_match_rest(),
+            Token::Label(label) => {
+                self.reader.expect(&Token::Op(Op::Colon))?;
+                self.reader.expect(&Token::Paren('{'))?;
+                Ok(Expr::LabeledBlock { label: label.to_string(), block: self.parse_block_rest()? })
+            }
+            Token::Break => self.parse_break_rest(),
+            Token::Op(Op::PipePipe) => self.parse_closure_body(Vec::new()),
+            Token::Op(Op::Pipe) => {
+                let params = self.parse_params(&Token::Op(Op::Pipe))?;
+                self.parse_closure_body(params)
+            }
+            Token::Fn => {
+                self.reader.expect(&Token::Paren('('))?;
+                let params = self.parse_params(&Token::Paren(')'))?;
+                self.reader.expect(&Token::Paren('{'))?;
+                let body = Expr::Block(self.parse_block_rest()?);
+                Ok(Expr::Closure { params, body: Box::new(body) })
+            }
+            other => Err(self.error_at_last(format!("Expected Expression, Found {:?}", other))),
+        }
+    }
+    /// Parses what follows `break`: an optional label, then a value unless
+    /// the expression ends right there.
+    fn parse_break_rest(&mut self) -> Result<Expr, ASTParsingError> {
+        let label = match self.reader.peek() {
+            Token::Label(label) => {
+                self.reader.next();
+                Some(label.to_string())
+            }
+            _ => None,
+        };
+        let value = match self.reader.peek() {
+            Token::Op(Op::Semi | Op::Comma) | Token::Paren(')' | ']' | '}') | Token::EOF => None,
+            _ => Some(Box::new(self.parse_expr()?)),
+        };
+        Ok(Expr::Break { label, value })
+    }
+    /// A `|` can only open a closure in expression position, anywhere else
+    /// it is reached through the binary operator loop as bitwise-or.
+    fn parse_closure_body(&mut self, params: Vec<Param>) -> Result<Expr, ASTParsingError> {
+        let body = self.parse_expr()?;
+        Ok(Expr::Closure { params, body: Box::new(body) })
+    }
+    /// Parses `name` or `name: Type` parameters up to and including `close`.
+    fn parse_params(&mut self, close: &Token) -> Result<Vec<Param>, ASTParsingError> {
+        self.parse_comma_list(close, |this| {
+            let name = this.parse_ident()?;
+            let ty = if this.reader.eat(&Token::Op(Op::Colon)) {
+                Some(this.parse_type()?)
+            } else {
+                None
+            };
+            Ok(Param { name, ty })
+        })
+    }
+    /// Parses the fields of a struct literal, up to and including the `}`.
+    /// A trailing `..base` fills in every field that wasn't listed.
+    fn parse_struct_literal_rest(&mut self, name: String) -> Result<Expr, ASTParsingError> {
+        let mut rest = None;
+        let fields = self.parse_comma_list(&Token::Paren('}'), |this| {
+            if rest.is_some() {
+                return Err(this.error(format!("'..' Must Come Last In The Literal Of '{name}'")));
+            }
+            if this.reader.eat(&Token::Op(Op::DotDot)) {
+                rest = Some(Box::new(this.parse_expr()?));
+                return Ok(None);
+            }
+            let field = this.parse_ident()?;
+            this.reader.expect(&Token::Op(Op::Colon))?;
+            Ok(Some((field, this.parse_expr()?)))
+        })?;
+        Ok(Expr::StructLit { name, fields: fields.into_iter().flatten().collect(), rest })
+    }
+    /// Parses what follows an `if` keyword. `elif` chains are folded into
+    /// nested `Expr::If`s in the else branch.
+    fn parse_if_rest(&mut self) -> Result<Expr, ASTParsingError> {
+        let let_pattern = self.parse_let_pattern()?;
+        let cond = self.with_struct_literals(false, Self::parse_expr)?;
+        self.reader.expect(&Token::Paren('{'))?;
+        let then = self.parse_block_rest()?;
+        let else_branch = self.parse_else()?;
+        Ok(match let_pattern {
+            Some(pattern) => Expr::IfLet { pattern: Box::new(pattern), scrutinee: Box::new(cond), then, else_branch },
+            None => Expr::If { cond: Box::new(cond), then, else_branch },
+        })
+    }
+    fn parse_else(&mut self) -> Result<Option<Box<Expr>>, ASTParsingError> {
+        Ok(if self.reader.eat(&Token::Elif) {
+            Some(Box::new(self.parse_if_rest()?))
+        } else if self.reader.eat(&Token::Else) {
+            self.reader.expect(&Token::Paren('{'))?;
+            Some(Box::new(Expr::Block(self.parse_block_rest()?)))
+        } else {
+            None
+        })
+    }
+    fn parse_while_rest(&mut self) -> Result<Expr, ASTParsingError> {
+        let let_pattern = self.parse_let_pattern()?;
+        let cond = self.with_struct_literals(false, Self::parse_expr)?;
+        self.reader.expect(&Token::Paren('{'))?;
+        let body = self.parse_block_rest()?;
+        Ok(match let_pattern {
+            Some(pattern) => Expr::WhileLet { pattern: Box::new(pattern), scrutinee: Box::new(cond), body },
+            None => Expr::While { cond: Box::new(cond), body },
+        })
+    }
+    /// Parses the `let pattern =` of an `if let` or `while let`, if present.
+    fn parse_let_pattern(&mut self) -> Result<Option<Pattern>, ASTParsingError> {
+        if !self.reader.eat(&Token::Let) {
+            return Ok(None);
+        }
+        let pattern = self.parse_pattern()?;
+        self.reader.expect(&Token::Op(Op::Eq))?;
+        Ok(Some(pattern))
+    }
+    fn parse_match_rest(&mut self) -> Result<Expr, ASTParsingError> {
+        let scrutinee = self.with_struct_literals(false, Self::parse_expr)?;
+        self.reader.expect(&Token::Paren('{'))?;
+        let mut arms = Vec::new();
+        while !self.reader.eat(&Token::Paren('}')) {
+            let arm = self.parse_arm()?;
+            let needs_comma = !arm.body.is_block_like();
+            arms.push(arm);
+            if !self.reader.eat(&Token::Op(Op::Comma)) && needs_comma {
+                self.reader.expect(&Token::Paren('}'))?;
+                break;
+            }
+        }
+        Ok(Expr::Match { scrutinee: Box::new(scrutinee), arms })
+    }
+    /// Parses `pattern [if guard] => body`.
+    fn parse_arm(&mut self) -> Result<Arm, ASTParsingError> {
+        let pattern = self.parse_pattern()?;
+        let guard = if self.reader.eat(&Token::If) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        self.reader.expect(&Token::Op(Op::FatArrow))?;
+        let body = self.with_struct_literals(true, Self::parse_expr)?;
+        Ok(Arm { pattern, guard, body })
+    }
+    pub fn parse_pattern(&mut self) -> Result<Pattern, ASTParsingError> {
+        self.nested(Self::parse_pattern_unnested)
+    }
+    fn parse_pattern_unnested(&mut self) -> Result<Pattern, ASTParsingError> {
+        match self.reader.next() {
+            Token::Ident(name) if name == "_" => Ok(Pattern::Wildcard),
+            Token::Ident(name) if self.reader.eat(&Token::Op(Op::At)) => Ok(Pattern::At {
+                name: name.to_string(),
+                pattern: Box::new(self.parse_pattern()?),
+            }),
+            Token::Ident(name) if self.reader.eat(&Token::Paren('(')) => {
+                let fields = self.parse_comma_list(&Token::Paren(')'), Self::parse_pattern)?;
+                Ok(Pattern::TupleStruct { name: name.to_string(), fields })
+            }
+            Token::Ident(name) => Ok(Pattern::Binding(name.to_string())),
+            token @ (Token::IntLit { .. } | Token::FloatLit { .. }) => Ok(Pattern::Literal(number_literal(token))),
+            Token::CharLit(ch) => Ok(Pattern::Literal(Expr::CharLit(*ch))),
+            Token::BoolLit(b) => Ok(Pattern::Literal(Expr::BoolLit(*b))),
+            Token::StrLit(s) => Ok(Pattern::Literal(Expr::StrLit(s.clone()))),
+            Token::ByteLit(ch) => Ok(Pattern::Literal(Expr::ByteLit(*ch))),
+            Token::ByteStrLit(s) => Ok(Pattern::Literal(Expr::ByteStrLit(s.clone()))),
+            other => Err(self.error_at_last(format!("Expected Pattern, Found {:?}", other))),
+        }
+    }
+    /// Parses the statements of a block after its opening `{`. The last
+    /// expression is the block's value unless it is followed by a `;`.
+    fn parse_block_rest(&mut self) -> Result<Block, ASTParsingError> {
+        self.with_struct_literals(true, |this| {
+            let mut stmts = Vec::new();
+            loop {
+                this.skip_doc_comments();
+                if this.reader.eat(&Token::Paren('}')) {
+                    return Ok(Block { stmts, tail: None });
+                }
+                let expr = this.parse_expr()?;
+                if this.reader.eat(&Token::Op(Op::Semi)) {
+                    stmts.push(Stmt::Expr(expr));
+                } else if this.reader.eat(&Token::Paren('}')) {
+                    return Ok(Block { stmts, tail: Some(Box::new(expr)) });
+                } else if expr.is_block_like() {
+                    stmts.push(Stmt::Expr(expr));
+                } else {
+                    return Err(this.error(format!("Expected ';' or '}}', Found {:?}", this.reader.peek())));
+                }
+            }
+        })
+    }
+
+    /// Whether only doc comments are left.
+    fn at_eof(&mut self) -> bool {
+        self.skip_doc_comments();
+        *self.reader.peek() == Token::EOF
+    }
+    /// Doc comments aren't kept in the AST, so they may come before any item
+    /// or statement.
+    fn skip_doc_comments(&mut self) {
+        while let Token::DocComment(..) = self.reader.peek() {
+            self.reader.next();
+        }
+    }
+    /// An error pointing at the next token.
+    fn error(&self, message: String) -> ASTParsingError {
+        ASTParsingError { message, offset: self.reader.offset() }
+    }
+    /// An error pointing at the token just read.
+    fn error_at_last(&self, message: String) -> ASTParsingError {
+        ASTParsingError { message, offset: self.reader.prev_offset() }
+    }
+    /// Runs `f` one level deeper, failing instead once `MAX_NESTING` levels
+    /// are open.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, ASTParsingError>) -> Result<T, ASTParsingError> {
+        if self.depth == MAX_NESTING {
+            return Err(self.error(format!("Nested More Than {MAX_NESTING} Levels Deep")));
+        }
+        self.depth += 1;
+        let ret = f(self);
+        self.depth -= 1;
+        ret
+    }
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        f: impl FnOnce(&mut Self) -> Result<T, ASTParsingError>,
+    ) -> Result<T, ASTParsingError> {
+        let prev = core::mem::replace(&mut self.struct_literals, allowed);
+        let ret = f(self);
+        self.struct_literals = prev;
+        ret
+    }
+
+    /// Parses a comma separated list of `f` up to and including `close`.
+    /// A trailing comma is allowed.
+    fn parse_comma_list<T>(
+        &mut self,
+        close: &Token,
+        mut f: impl FnMut(&mut Self) -> Result<T, ASTParsingError>,
+    ) -> Result<Vec<T>, ASTParsingError> {
+        let mut ret = Vec::new();
+        while !self.reader.eat(close) {
+            ret.push(f(self)?);
+            if !self.reader.eat(&Token::Op(Op::Comma)) {
+                self.reader.expect(close)?;
+                break;
+            }
+        }
+        Ok(ret)
+    }
+    pub fn parse_type(&mut self) -> Result<Type, ASTParsingError> {
+        self.nested(Self::parse_type_unnested)
+    }
+    fn parse_type_unnested(&mut self) -> Result<Type, ASTParsingError> {
+        if !self.reader.eat(&Token::Paren('[')) {
+            let name = self.parse_ident()?;
+            if *self.reader.peek() == Token::Op(Op::ColonColon) {
+                return Ok(Type::Path(self.parse_path_rest(name)?));
+            }
+            return Ok(Type::Named(name));
+        }
+        let elem = Box::new(self.parse_type()?);
+        if self.reader.eat(&Token::Paren(']')) {
+            return Ok(Type::Slice(elem));
+        }
+        self.reader.expect(&Token::Op(Op::Semi))?;
+        let len = Box::new(self.with_struct_literals(true, Self::parse_expr)?);
+        self.reader.expect(&Token::Paren(']'))?;
+        Ok(Type::Array { elem, len })
+    }
+    /// Parses the `::b::c` following the first segment of a path.
+    fn parse_path_rest(&mut self, first: String) -> Result<Path, ASTParsingError> {
+        let mut segments = vec![first];
+        while self.reader.eat(&Token::Op(Op::ColonColon)) {
+            segments.push(self.parse_ident()?);
+        }
+        Ok(Path { segments })
+    }
+    /// A named field, or a tuple index like the `0` of `t.0`.
+    fn parse_field_name(&mut self) -> Result<String, ASTParsingError> {
+        match self.reader.peek() {
+            Token::IntLit { digits, suffix: None } => {
+                self.reader.next();
+                Ok(digits.to_string())
+            }
+            _ => self.parse_ident(),
+        }
+    }
+    fn parse_ident(&mut self) -> Result<String, ASTParsingError> {
+        match self.reader.next() {
+            Token::Ident(name) => Ok(name.to_string()),
+            other => Err(self.error_at_last(format!("Expected Identifier, Found {:?}", other))),
+        }
+    }
+}
+
+fn number_literal(token: &Token) -> Expr {
+    match *token {
+        Token::IntLit { digits, suffix } => Expr::IntLit { digits: digits.to_string(), suffix: suffix.map(String::from) },
+        Token::FloatLit { digits, suffix } => Expr::FloatLit { digits: digits.to_string(), suffix: suffix.map(String::from) },
+        _ => unreachable!(),
     }
 }
 
+fn is_item_start(token: &Token) -> bool {
+    matches!(token, Token::Const | Token::Static | Token::Enum | Token::Fn)
+}
+
+/// Binding power of each binary operator, higher binds tighter.
+fn binary_precedence(op: Op) -> Option<u8> {
+    Some(match op {
+        Op::PipePipe => 1,
+        Op::AmpAmp => 2,
+        Op::EqEq | Op::BangEq | Op::Lt | Op::Gt | Op::Le | Op::Ge => 3,
+        Op::Pipe => 4,
+        Op::Caret => 5,
+        Op::Amp => 6,
+        Op::Shl | Op::Shr => 7,
+        Op::Plus | Op::Minus => 8,
+        Op::Star | Op::Slash | Op::Percent => 9,
+        _ => return None,
+    })
+}
+
 pub struct ASTParsingError {
+    message: String,
+    /// Byte offset in the source the error points at, if the parser was
+    /// given token offsets.
+    offset: Option<usize>,
+}
+impl ASTParsingError {
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+impl From<UnexpectedToken> for ASTParsingError {
+    fn from(err: UnexpectedToken) -> Self {
+        ASTParsingError { message: err.message().to_string(), offset: err.offset() }
+    }
+}
+impl Error for ASTParsingError {}
+impl Display for ASTParsingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl Debug for ASTParsingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ASTParsingError: {}", self.message)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Const { name: String, ty: Type, value: Expr },
+    Static { name: String, ty: Type, value: Expr },
+    Enum { name: String, variants: Vec<Variant> },
+    Fn { name: String, params: Vec<Param>, body: FnBody },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FnBody {
+    Block(Block),
+    Expr(Expr),
+}
 
+#[derive(Debug, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub fields: VariantFields,
 }
 
-pub struct ASTNode {
-    
+#[derive(Debug, PartialEq)]
+pub enum VariantFields {
+    Unit,
+    Tuple(Vec<Type>),
+    Struct(Vec<(String, Type)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Ident(String),
+    Path(Path),
+    IntLit { digits: String, suffix: Option<String> },
+    FloatLit { digits: String, suffix: Option<String> },
+    CharLit(u8),
+    BoolLit(bool),
+    StrLit(Vec<u8>),
+    ByteLit(u8),
+    ByteStrLit(Vec<u8>),
+    Undefined,
+
+    Unary { op: Op, expr: Box<Expr> },
+    Binary { op: Op, lhs: Box<Expr>, rhs: Box<Expr> },
+
+    Field { expr: Box<Expr>, name: String },
+    Call { callee: Box<Expr>, args: Vec<Expr> },
+    Index { expr: Box<Expr>, index: Box<Expr> },
+    Try(Box<Expr>),
+    Cast { expr: Box<Expr>, ty: Type },
+
+    StructLit { name: String, fields: Vec<(String, Expr)>, rest: Option<Box<Expr>> },
+    Block(Block),
+    If { cond: Box<Expr>, then: Block, else_branch: Option<Box<Expr>> },
+    IfLet { pattern: Box<Pattern>, scrutinee: Box<Expr>, then: Block, else_branch: Option<Box<Expr>> },
+    While { cond: Box<Expr>, body: Block },
+    WhileLet { pattern: Box<Pattern>, scrutinee: Box<Expr>, body: Block },
+    Closure { params: Vec<Param>, body: Box<Expr> },
+    Match { scrutinee: Box<Expr>, arms: Vec<Arm> },
+    LabeledBlock { label: String, block: Block },
+    Break { label: Option<String>, value: Option<Box<Expr>> },
+}
+impl Expr {
+    /// Block-like expressions may end a statement or match arm without a
+    /// separator.
+    fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Expr::Block(_)
+                | Expr::If { .. }
+                | Expr::IfLet { .. }
+                | Expr::While { .. }
+                | Expr::WhileLet { .. }
+                | Expr::Match { .. }
+                | Expr::LabeledBlock { .. }
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Literal(Expr),
+    TupleStruct { name: String, fields: Vec<Pattern> },
+    /// `name @ pattern` binds the whole value while also matching it.
+    At { name: String, pattern: Box<Pattern> },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub tail: Option<Box<Expr>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<Type>,
+}
+
+/// `a::b::c`, always with at least two segments. A single name stays an
+/// `Expr::Ident` or `Type::Named`.
+#[derive(Debug, PartialEq)]
+pub struct Path {
+    pub segments: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Type {
+    Named(String),
+    Path(Path),
+    /// `[T]`
+    Slice(Box<Type>),
+    /// `[T; N]`
+    Array { elem: Box<Type>, len: Box<Expr> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Stmt {
+    Expr(Expr),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Box<Expr> {
+        Box::new(Expr::Ident(name.into()))
+    }
+    /// Parses `source` as the body of `fn f() = source;`.
+    fn expr(source: &str) -> Expr {
+        let (mut items, errors) = parse_program(&format!("fn f() = {source};"));
+        assert!(errors.is_empty(), "{errors:?}");
+        match items.pop() {
+            Some(Item::Fn { body: FnBody::Expr(expr), .. }) => expr,
+            other => panic!("{other:?}"),
+        }
+    }
+
+    fn nested_parens(depth: usize) -> String {
+        format!("const X: Int = {}1{};", "(".repeat(depth), ")".repeat(depth))
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_a_stack_overflow() {
+        let (items, errors) = parse_program(&nested_parens(MAX_NESTING - 1));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(items.len(), 1);
+        for source in [nested_parens(100_000), format!("const X: Int = {}1;", "!".repeat(100_000))] {
+            let (items, errors) = parse_program(&source);
+            assert!(items.is_empty());
+            assert!(errors[0].to_string().contains("Nested More Than"), "{errors:?}");
+        }
+    }
+
+    #[test]
+    fn parser_errors_point_at_the_offending_token() {
+        let source = "fn f( = 1;";
+        let (_, errors) = parse_program(source);
+        assert_eq!(errors[0].offset(), Some(6), "{errors:?}");
+        let out = crate::frontend::error::report(source, &errors);
+        assert!(out.contains("--> 1:7\n"), "{out}");
+        assert!(out.contains("1 | fn f( = 1;\n  |       ^\n"), "{out}");
+    }
+
+    #[test]
+    fn strict_ops_lints_come_before_parse_errors() {
+        let source = "fn f(a, b) = a == = b;";
+        let (items, errors) = parse_program_with(source, true);
+        assert!(items.is_empty());
+        let FrontendError::Lexing(lint) = &errors[0] else { panic!("{errors:?}") };
+        assert!(matches!(lint.kind(), LexErrorKind::AdjacentOps { .. }), "{lint:?}");
+        assert!(matches!(errors[1], FrontendError::Parsing(_)), "{errors:?}");
+        assert_eq!(parse_program_with(source, false).1.len(), 1);
+    }
+
+    #[test]
+    fn tuple_fields_are_integer_names() {
+        let field = |expr, name: &str| Box::new(Expr::Field { expr, name: name.into() });
+        assert_eq!(expr("t.0"), *field(ident("t"), "0"));
+        assert_eq!(expr("t.0.1"), *field(field(ident("t"), "0"), "1"));
+        assert_eq!(expr("t.0.y"), *field(field(ident("t"), "0"), "y"));
+        assert_eq!(parse_program("fn f() = t.0u8;").1.len(), 1);
+    }
+
+    #[test]
+    fn doc_comments_are_skipped() {
+        let source = "/// Adds one.\nfn f(x) = x + 1;\n\
+            fn g() {\n    /** Says hi. */\n    hi();\n    /// Done.\n}\n/// Trailing.";
+        let (items, errors) = parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(items.len(), 2);
+        let call = Expr::Call { callee: ident("hi"), args: Vec::new() };
+        let body = FnBody::Block(Block { stmts: [Stmt::Expr(call)].into(), tail: None });
+        assert_eq!(items[1], Item::Fn { name: "g".into(), params: Vec::new(), body });
+    }
+
+    #[test]
+    fn items_around_a_lexing_error_are_kept() {
+        let (items, errors) = parse_program("fn a() = `; fn b() = 1;");
+        let one = Expr::IntLit { digits: "1".into(), suffix: None };
+        assert_eq!(items, [Item::Fn { name: "b".into(), params: Vec::new(), body: FnBody::Expr(one) }]);
+        let FrontendError::Lexing(err) = &errors[0] else { panic!("{errors:?}") };
+        assert!(matches!(err.kind(), LexErrorKind::IllegalChar(b'`')), "{err}");
+        assert_eq!(err.offset(), Some(9));
+    }
+
+    #[test]
+    fn guard_comparisons_stay_apart_from_the_fat_arrow() {
+        let zero = || Expr::IntLit { digits: "0".into(), suffix: None };
+        let guard = Expr::Binary { op: Op::Ge, lhs: ident("n"), rhs: Box::new(zero()) };
+        let arms = [
+            Arm { pattern: Pattern::Binding("n".into()), guard: Some(guard), body: *ident("n") },
+            Arm { pattern: Pattern::Wildcard, guard: None, body: zero() },
+        ];
+        assert_eq!(expr("match x { n if n >= 0 => n, _ => 0 }"), Expr::Match { scrutinee: ident("x"), arms: arms.into() });
+        assert_eq!(expr("match x { n if n>=0=>n, _=>0 }"), expr("match x { n if n >= 0 => n, _ => 0 }"));
+    }
+
+    #[test]
+    fn try_interleaves_with_calls_and_fields() {
+        let call = |callee| Box::new(Expr::Call { callee, args: Vec::new() });
+        let field = |expr, name: &str| Box::new(Expr::Field { expr, name: name.into() });
+        let try_ = |expr: Box<Expr>| Box::new(Expr::Try(expr));
+        assert_eq!(expr("a.b()?"), *try_(call(field(ident("a"), "b"))));
+        assert_eq!(expr("f()?.g()"), *call(field(try_(call(ident("f"))), "g")));
+        assert_eq!(expr("x?"), *try_(ident("x")));
+        assert_eq!(expr("a.b()?.c()?"), *try_(call(field(try_(call(field(ident("a"), "b"))), "c"))));
+    }
+
+    #[test]
+    fn struct_literals_are_off_in_conditions() {
+        let one = Expr::IntLit { digits: "1".into(), suffix: None };
+        let point = Expr::StructLit { name: "Point".into(), fields: [("x".into(), one)].into(), rest: None };
+        assert_eq!(expr("Point { x: 1 }"), point);
+        let empty = Block { stmts: Vec::new(), tail: None };
+        assert_eq!(expr("if x {}"), Expr::If { cond: ident("x"), then: empty, else_branch: None });
+        // Parens turn them back on.
+        let Expr::If { cond, .. } = expr("if (Point { x: 1 }) {}") else { panic!() };
+        assert_eq!(*cond, point);
+    }
+
+    #[test]
+    fn closures_in_both_forms() {
+        let param = |name: &str| Param { name: name.into(), ty: None };
+        let closure = |params: &[&str], body| Expr::Closure { params: params.iter().map(|p| param(p)).collect(), body };
+        assert_eq!(expr("|x| x"), closure(&["x"], ident("x")));
+        let sum = Expr::Binary { op: Op::Plus, lhs: ident("x"), rhs: ident("y") };
+        assert_eq!(expr("|x, y| x + y"), closure(&["x", "y"], Box::new(sum)));
+        let block = Expr::Block(Block { stmts: Vec::new(), tail: Some(ident("x")) });
+        assert_eq!(expr("fn(x) { x }"), closure(&["x"], Box::new(block)));
+        let Expr::Closure { params, .. } = expr("|x: Int| x") else { panic!() };
+        assert_eq!(params, [Param { name: "x".into(), ty: Some(Type::Named("Int".into())) }]);
+    }
+
+    #[test]
+    fn const_and_static_need_a_type_and_a_value() {
+        let (items, errors) = parse_program("const X: Int = 1; static Y: Int = 2;");
+        assert!(errors.is_empty(), "{errors:?}");
+        let int = || Type::Named("Int".into());
+        let lit = |digits: &str| Expr::IntLit { digits: digits.into(), suffix: None };
+        assert_eq!(
+            items,
+            [
+                Item::Const { name: "X".into(), ty: int(), value: lit("1") },
+                Item::Static { name: "Y".into(), ty: int(), value: lit("2") },
+            ]
+        );
+        let (items, errors) = parse_program("const X: Int;");
+        assert!(items.is_empty());
+        assert_eq!(errors[0].to_string(), "parsing error: Const Item 'X' Is Missing Its Value");
+    }
+
+    #[test]
+    fn arms_with_and_without_guards() {
+        let zero = || Expr::IntLit { digits: "0".into(), suffix: None };
+        let positive = Expr::Binary { op: Op::Gt, lhs: ident("n"), rhs: Box::new(zero()) };
+        let some = Pattern::TupleStruct { name: "Some".into(), fields: [Pattern::Binding("v".into())].into() };
+        let arms = [
+            Arm { pattern: Pattern::Binding("n".into()), guard: Some(positive), body: *ident("n") },
+            Arm { pattern: Pattern::At { name: "s".into(), pattern: Box::new(some) }, guard: None, body: *ident("v") },
+            Arm { pattern: Pattern::Wildcard, guard: None, body: zero() },
+        ];
+        let source = "match x { n if n > 0 => n, s @ Some(v) => v, _ => 0 }";
+        assert_eq!(expr(source), Expr::Match { scrutinee: ident("x"), arms: arms.into() });
+    }
+
+    #[test]
+    fn enum_variants_of_every_form() {
+        let (items, errors) = parse_program("enum E { A, B(Int, T), C { x: Int }, } enum Empty {}");
+        assert!(errors.is_empty(), "{errors:?}");
+        let ty = |name: &str| Type::Named(name.into());
+        let variants = [
+            Variant { name: "A".into(), fields: VariantFields::Unit },
+            Variant { name: "B".into(), fields: VariantFields::Tuple([ty("Int"), ty("T")].into()) },
+            Variant { name: "C".into(), fields: VariantFields::Struct([("x".into(), ty("Int"))].into()) },
+        ];
+        assert_eq!(
+            items,
+            [
+                Item::Enum { name: "E".into(), variants: variants.into() },
+                Item::Enum { name: "Empty".into(), variants: Vec::new() },
+            ]
+        );
+    }
+
+    #[test]
+    fn if_let_and_while_let() {
+        let some_x = || {
+            Box::new(Pattern::TupleStruct { name: "Some".into(), fields: [Pattern::Binding("x".into())].into() })
+        };
+        let empty = || Block { stmts: Vec::new(), tail: None };
+        assert_eq!(
+            expr("if let Some(x) = opt {}"),
+            Expr::IfLet { pattern: some_x(), scrutinee: ident("opt"), then: empty(), else_branch: None }
+        );
+        assert_eq!(
+            expr("while let Some(x) = it {}"),
+            Expr::WhileLet { pattern: some_x(), scrutinee: ident("it"), body: empty() }
+        );
+    }
+
+    #[test]
+    fn slice_and_array_types() {
+        let type_of = |ty: &str| match parse_program(&format!("const X: {ty} = x;")).0.pop() {
+            Some(Item::Const { ty, .. }) => ty,
+            other => panic!("{other:?}"),
+        };
+        let int = || Box::new(Type::Named("Int".into()));
+        assert_eq!(type_of("[Int]"), Type::Slice(int()));
+        let four = Box::new(Expr::IntLit { digits: "4".into(), suffix: None });
+        assert_eq!(type_of("[Int; 4]"), Type::Array { elem: int(), len: four });
+        assert_eq!(type_of("[[Int]; n]"), Type::Array { elem: Box::new(Type::Slice(int())), len: ident("n") });
+    }
+
+    #[test]
+    fn fn_bodies_are_blocks_or_expressions() {
+        let (items, errors) = parse_program("fn f(x) { x } fn g(x) = x;");
+        assert!(errors.is_empty(), "{errors:?}");
+        let params = || [Param { name: "x".into(), ty: None }].into();
+        let block = Block { stmts: Vec::new(), tail: Some(ident("x")) };
+        assert_eq!(
+            items,
+            [
+                Item::Fn { name: "f".into(), params: params(), body: FnBody::Block(block) },
+                Item::Fn { name: "g".into(), params: params(), body: FnBody::Expr(*ident("x")) },
+            ]
+        );
+        let (items, errors) = parse_program("fn f() x;");
+        assert!(items.is_empty());
+        assert!(
+            errors[0].to_string().contains("Expected '{' Or '=' After The Signature Of 'f', Found Ident"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn struct_update_comes_last() {
+        let one = Expr::IntLit { digits: "1".into(), suffix: None };
+        assert_eq!(
+            expr("Point { x: 1, ..p }"),
+            Expr::StructLit { name: "Point".into(), fields: [("x".into(), one)].into(), rest: Some(ident("p")) }
+        );
+        let (_, errors) = parse_program("fn f() = Point { ..p, x: 1 };");
+        assert!(errors[0].to_string().contains("'..' Must Come Last In The Literal Of 'Point'"), "{errors:?}");
+    }
+
+    #[test]
+    fn casts_bind_looser_than_unary_and_chain_left() {
+        let cast = |expr, ty: &str| Box::new(Expr::Cast { expr, ty: Type::Named(ty.into()) });
+        assert_eq!(expr("x as Int"), *cast(ident("x"), "Int"));
+        let neg = Box::new(Expr::Unary { op: Op::Minus, expr: ident("x") });
+        assert_eq!(expr("-x as Int"), *cast(neg, "Int"));
+        assert_eq!(expr("x as A as B"), *cast(cast(ident("x"), "A"), "B"));
+        let sum = Expr::Binary { op: Op::Plus, lhs: ident("a"), rhs: cast(ident("b"), "Int") };
+        assert_eq!(expr("a + b as Int"), sum);
+    }
+
+    #[test]
+    fn labeled_block_breaks_with_a_value() {
+        let one = Expr::IntLit { digits: "1".into(), suffix: None };
+        let brk = Expr::Break { label: Some("out".into()), value: Some(Box::new(one)) };
+        let block = Block { stmts: [Stmt::Expr(brk)].into(), tail: Some(ident("x")) };
+        assert_eq!(expr("'out: { break 'out 1; x }"), Expr::LabeledBlock { label: "out".into(), block });
+        let Expr::LabeledBlock { block, .. } = expr("'a: { break 'a }") else { panic!() };
+        assert_eq!(block.tail, Some(Box::new(Expr::Break { label: Some("a".into()), value: None })));
+    }
+
+    #[test]
+    fn paths_in_expressions_and_types() {
+        let path = |segments: &[&str]| Path { segments: segments.iter().map(|s| s.to_string()).collect() };
+        assert_eq!(expr("a::b"), Expr::Path(path(&["a", "b"])));
+        assert_eq!(expr("a::b::c"), Expr::Path(path(&["a", "b", "c"])));
+        let callee = Box::new(Expr::Path(path(&["a", "b", "f"])));
+        assert_eq!(expr("a::b::f()"), Expr::Call { callee, args: Vec::new() });
+        let Some(Item::Const { ty, .. }) = parse_program("const X: std::Int = 1;").0.pop() else { panic!() };
+        assert_eq!(ty, Type::Path(path(&["std", "Int"])));
+    }
 }
@@ -0,0 +1,173 @@
+use core::{error::Error, fmt::{Debug, Display, Write}};
+use alloc::{string::{String, ToString}, vec::Vec};
+#[cfg(feature = "std")]
+use std::io;
+
+use super::lexer::{next_col, LexingError, DEFAULT_TAB_WIDTH};
+use super::ast_parser::ASTParsingError;
+
+/// Any error produced by the frontend, so callers can `?` lexer and parser
+/// results into one type.
+#[derive(Debug)]
+pub enum FrontendError {
+    Lexing(LexingError),
+    Parsing(ASTParsingError),
+}
+impl Error for FrontendError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FrontendError::Lexing(err) => Some(err),
+            FrontendError::Parsing(err) => Some(err),
+        }
+    }
+}
+impl Display for FrontendError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FrontendError::Lexing(err) => write!(f, "lexing error: {}", err),
+            FrontendError::Parsing(err) => write!(f, "parsing error: {}", err),
+        }
+    }
+}
+
+impl FrontendError {
+    /// Byte offset in the source the error points at, if it has one.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            FrontendError::Lexing(err) => err.offset(),
+            FrontendError::Parsing(err) => err.offset(),
+        }
+    }
+}
+
+/// Renders every diagnostic in source order, each with the offending line
+/// and a caret under it where the position is known, then a count.
+pub fn report(source: &str, diags: &[FrontendError]) -> String {
+    let mut sorted: Vec<&FrontendError> = diags.iter().collect();
+    // Diagnostics without a position go last, in the order they came.
+    sorted.sort_by_key(|diag| diag.offset().unwrap_or(usize::MAX));
+    let mut out = String::new();
+    for diag in sorted {
+        writeln!(out, "error: {diag}").unwrap();
+        if let Some(offset) = diag.offset() {
+            let tab_width = match diag {
+                FrontendError::Lexing(err) => err.tab_width(),
+                FrontendError::Parsing(_) => DEFAULT_TAB_WIDTH,
+            };
+            write_snippet(&mut out, source, offset, tab_width, None);
+        }
+    }
+    let count = diags.len();
+    write!(out, "{count} error{}", if count == 1 { "" } else { "s" }).unwrap();
+    out
+}
+
+/// Writes the `--> file:line:col` location of `offset`, then its line with a
+/// caret under it.
+pub(crate) fn write_snippet(out: &mut String, source: &str, offset: usize, tab_width: usize, filename: Option<&str>) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    // `\r\n` and a lone `\r` end a line as well as `\n`.
+    let line_start = source[..offset].rfind(['\n', '\r']).map_or(0, |i| i + 1);
+    let line_end = source[offset..].find(['\n', '\r']).map_or(source.len(), |i| offset + i);
+    let line_no = source[..offset].replace("\r\n", "\n").matches(['\n', '\r']).count() + 1;
+    let before = &source[line_start..offset];
+    let gutter = " ".repeat(line_no.to_string().len());
+    // Tabs are kept so the caret lines up however they are displayed.
+    let pad: String = before.chars().map(|ch| if ch == '\t' { '\t' } else { ' ' }).collect();
+    // Counted the way the lexer counts columns, so they match token spans.
+    let col = before.bytes().fold(1, |col, ch| next_col(col, ch, tab_width));
+    match filename {
+        Some(filename) => writeln!(out, "{gutter}--> {filename}:{line_no}:{col}").unwrap(),
+        None => writeln!(out, "{gutter}--> {line_no}:{col}").unwrap(),
+    }
+    writeln!(out, "{gutter} |").unwrap();
+    writeln!(out, "{line_no} | {}", &source[line_start..line_end]).unwrap();
+    writeln!(out, "{gutter} | {pad}^").unwrap();
+}
+
+
+impl From<LexingError> for FrontendError {
+    fn from(err: LexingError) -> Self {
+        FrontendError::Lexing(err)
+    }
+}
+impl From<ASTParsingError> for FrontendError {
+    fn from(err: ASTParsingError) -> Self {
+        FrontendError::Parsing(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<LexingError> for io::Error {
+    fn from(err: LexingError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+#[cfg(feature = "std")]
+impl From<FrontendError> for io::Error {
+    fn from(err: FrontendError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+
+    #[test]
+    fn report_renders_every_diagnostic_and_a_count() {
+        let source = "let a = `;\nlet b = `;";
+        // Blanking the first backtick moves the error to the second one
+        // without shifting any offsets.
+        let second = source.replacen('`', " ", 1);
+        let diags = [
+            FrontendError::from(Lexer::new(&second).lex().unwrap_err()),
+            FrontendError::from(Lexer::new(source).lex().unwrap_err()),
+        ];
+        let out = report(source, &diags);
+        assert_eq!(out.lines().filter(|line| line.starts_with("error: ")).count(), 2, "{out}");
+        assert_eq!(out.matches("        ^").count(), 2, "{out}");
+        assert!(out.find("1:9").unwrap() < out.find("2:9").unwrap(), "{out}");
+        assert!(out.ends_with("2 errors"), "{out}");
+    }
+
+    #[test]
+    fn snippet_column_matches_the_lexer() {
+        let source = "\tx = `";
+        let out = Lexer::new(source).lex().unwrap_err().render(source);
+        assert!(out.contains("--> 1:9\n"), "{out}");
+        let spans = Lexer::new("\tx = y").lex_spanned().unwrap();
+        assert_eq!(spans[2].1.start_col, 9);
+    }
+
+    #[test]
+    fn snippet_column_uses_the_lexers_tab_width() {
+        let source = "\tx = `";
+        let err = Lexer::new(source).tab_width(8).lex().unwrap_err();
+        assert!(err.render(source).contains("--> 1:13\n"), "{}", err.render(source));
+        let out = report(source, &[err.into()]);
+        assert!(out.contains("--> 1:13\n"), "{out}");
+        let spans = Lexer::new("\tx = y").tab_width(8).lex_spanned().unwrap();
+        assert_eq!(spans[2].1.start_col, 13);
+    }
+
+    #[test]
+    fn both_errors_convert_and_format() {
+        let lexing = FrontendError::from(Lexer::new("a `").lex().unwrap_err());
+        assert_eq!(lexing.to_string(), "lexing error: Illegal Character '`' (0x60) At Offset 2");
+        assert_eq!(lexing.offset(), Some(2));
+        assert!(lexing.source().is_some());
+        let parsing = crate::frontend::ast_parser::parse_program("fn f(").1.pop().unwrap();
+        assert!(matches!(parsing, FrontendError::Parsing(_)));
+        assert!(parsing.to_string().starts_with("parsing error: Expected Identifier"), "{parsing}");
+        #[cfg(feature = "std")]
+        {
+            let io = io::Error::from(parsing);
+            assert_eq!(io.kind(), io::ErrorKind::InvalidData);
+            assert!(io.to_string().starts_with("parsing error: "), "{io}");
+        }
+    }
+}
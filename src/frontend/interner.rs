@@ -0,0 +1,93 @@
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// An interned identifier. Two symbols from the same `Interner` are equal
+/// exactly when their text is.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Symbol(pub u32);
+
+/// Rendered as `#n`, since the text lives in the interner.
+impl core::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Hands out one `Symbol` per distinct identifier, numbered in the order
+/// they were first seen.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<String, Symbol>,
+    names: Vec<String>,
+}
+#[cfg(feature = "std")]
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.symbols.insert(name.to_string(), symbol);
+        self.names.push(name.to_string());
+        symbol
+    }
+    /// The symbol for `name` if it has been interned, without adding it.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+    /// Panics if `symbol` came from a different interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    /// A checkpoint to roll back to with `restore`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.names.len())
+    }
+    /// Forgets every name interned since `snapshot` was taken, so their
+    /// symbols may be handed out again. Names interned before it keep
+    /// their symbols.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        let keep = snapshot.0.min(self.names.len());
+        for name in self.names.drain(keep..) {
+            self.symbols.remove(&name);
+        }
+    }
+}
+
+/// The size of an `Interner` at some point, from `Interner::snapshot`.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Snapshot(usize);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_forgets_names_interned_after_the_snapshot() {
+        let mut interner = Interner::new();
+        let a = interner.intern("main");
+        let shared = interner.intern("x");
+        let snapshot = interner.snapshot();
+        let b = interner.intern("helper");
+        assert_eq!(interner.intern("x"), shared);
+        interner.restore(snapshot);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get("helper"), None);
+        assert_eq!(interner.get("main"), Some(a));
+        assert_eq!(interner.resolve(shared), "x");
+        // The rolled back symbol is free for the next new name.
+        assert_eq!(interner.intern("other"), b);
+    }
+}
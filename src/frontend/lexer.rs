@@ -1,9 +1,19 @@
-use core::fmt::Debug;
-use std::{str::from_utf8, error::Error, fmt::Display};
+use core::{error::Error, fmt::{Debug, Display}, str::from_utf8};
+use alloc::{borrow::Cow, collections::BTreeMap, format, string::{String, ToString}, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
-pub enum Token {
+#[cfg(feature = "std")]
+use super::interner::Interner;
+use super::interner::Symbol;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Token<'a> {
     EOF,
+    /// One or more line breaks, when `Lexer::significant_newlines` is on.
+    Newline,
 
     Fn,
     Let,
@@ -14,19 +24,726 @@ pub enum Token {
     Else,
     Mod,
     Struct,
+    Const,
+    Static,
+    Match,
+    Enum,
+    While,
+    As,
+    Break,
+    For,
+    Return,
+    Continue,
+    /// Any keyword, when `Lexer::keyword_tokens` is on.
+    Keyword(Keyword),
+    /// A keyword registered through `LexerBuilder::keyword`.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    CustomKeyword(Cow<'a, str>),
 
-    Sign(&'static str),
+    Op(Op),
+    /// An operator registered through `LexerBuilder::custom_op`.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    CustomOp(Cow<'a, str>),
     Paren(char),
 
-    NumLit(String),
+    /// `digits` is the literal as written, prefix and separators included,
+    /// and `suffix` its type if one follows, as in `10u8`.
+    IntLit {
+        digits: &'a str,
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        suffix: Option<&'a str>,
+    },
+    FloatLit {
+        digits: &'a str,
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        suffix: Option<&'a str>,
+    },
+    /// An integer literal's magnitude, when `Lexer::parse_numbers` is on.
+    IntValue {
+        value: u128,
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        suffix: Option<&'a str>,
+    },
+    /// A float literal's value, when `Lexer::parse_numbers` is on.
+    FloatValue {
+        value: f64,
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        suffix: Option<&'a str>,
+    },
     CharLit(u8),
+    BoolLit(bool),
     StrLit(Vec<u8>),
+    /// `b'x'`, which must be ASCII.
+    ByteLit(u8),
+    /// `b"..."`, which must be ASCII and is never lazy.
+    ByteStrLit(Vec<u8>),
+    LazyStrLit(LazyStr),
+
+    /// Borrowed from the source unless normalization had to rewrite it.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    Ident(Cow<'a, str>),
+    /// An identifier, from `Lexer::lex_interned`.
+    InternedIdent(Symbol),
+    /// `'name`, naming a block for `break 'name`.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    Label(Cow<'a, str>),
+    /// A run of two or more underscores, when `Lexer::placeholders` is on.
+    Placeholder(usize),
+
+    DocComment(DocStyle, DocForm, String),
+    /// An ordinary comment without its delimiters, when
+    /// `Lexer::preserve_comments` is on.
+    LineComment(String),
+    BlockComment(String),
+}
+
+/// Every operator and punctuation sign the lexer knows about.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Op {
+    PlusPlus, PlusEq, Plus,
+    MinusMinus, MinusEq, Arrow, Minus,
+    StarStarEq, StarStar, StarEq, Star,
+    SlashEq, Slash,
+    PercentEq, Percent,
+    BangEq, Bang,
+    ColonEq, ColonColon, Colon,
+    EqEq, FatArrow, Eq,
+    AmpAmp, AmpEq, Amp,
+    PipePipe, PipeEq, Pipe,
+    TildeEq, Tilde,
+    CaretEq, Caret,
+    ShlEq, Shl, Le, Lt,
+    ShrEq, Shr, Ge, Gt,
+    DotDotEq, DotDot, Dot,
+    HashBang, Hash,
+    Comma, Semi, Question, Dollar, At,
+}
+impl Op {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Op::PlusPlus => "++",
+            Op::PlusEq => "+=",
+            Op::Plus => "+",
+            Op::MinusMinus => "--",
+            Op::MinusEq => "-=",
+            Op::Arrow => "->",
+            Op::Minus => "-",
+            Op::StarStarEq => "**=",
+            Op::StarStar => "**",
+            Op::StarEq => "*=",
+            Op::Star => "*",
+            Op::SlashEq => "/=",
+            Op::Slash => "/",
+            Op::PercentEq => "%=",
+            Op::Percent => "%",
+            Op::BangEq => "!=",
+            Op::Bang => "!",
+            Op::ColonEq => ":=",
+            Op::ColonColon => "::",
+            Op::Colon => ":",
+            Op::EqEq => "==",
+            Op::FatArrow => "=>",
+            Op::Eq => "=",
+            Op::AmpAmp => "&&",
+            Op::AmpEq => "&=",
+            Op::Amp => "&",
+            Op::PipePipe => "||",
+            Op::PipeEq => "|=",
+            Op::Pipe => "|",
+            Op::TildeEq => "~=",
+            Op::Tilde => "~",
+            Op::CaretEq => "^=",
+            Op::Caret => "^",
+            Op::ShlEq => "<<=",
+            Op::Shl => "<<",
+            Op::Le => "<=",
+            Op::Lt => "<",
+            Op::ShrEq => ">>=",
+            Op::Shr => ">>",
+            Op::Ge => ">=",
+            Op::Gt => ">",
+            Op::DotDotEq => "..=",
+            Op::DotDot => "..",
+            Op::Dot => ".",
+            Op::HashBang => "#!",
+            Op::Hash => "#",
+            Op::Comma => ",",
+            Op::Semi => ";",
+            Op::Question => "?",
+            Op::Dollar => "$",
+            Op::At => "@",
+        }
+    }
+}
+impl From<Op> for &'static str {
+    fn from(op: Op) -> Self {
+        op.as_str()
+    }
+}
+
+/// The variant of a `Token` without its payload.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenKind {
+    EOF,
+    Newline,
+
+    Fn,
+    Let,
+    Var,
+    Undefined,
+    If,
+    Elif,
+    Else,
+    Mod,
+    Struct,
+    Const,
+    Static,
+    Match,
+    Enum,
+    While,
+    As,
+    Break,
+    For,
+    Return,
+    Continue,
+    CustomKeyword,
+
+    Op,
+    CustomOp,
+    Paren,
+
+    IntLit,
+    FloatLit,
+    CharLit,
+    BoolLit,
+    ByteLit,
+    ByteStrLit,
+    StrLit,
+
+    Ident,
+    Label,
+    Placeholder,
+
+    DocComment,
+    LineComment,
+    BlockComment,
+}
+
+/// Where a token sits in the source: the byte range `start..end`, and the
+/// 1-based line and column (in bytes, with a tab going to the next tab
+/// stop) it starts at and ends before.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+/// Whitespace and comments the lexer skipped between two tokens.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriviaSpan(pub Span);
+
+/// A token with its span and the source text skipped right before it,
+/// from `Lexer::lex_with_trivia`.
+#[derive(Debug, PartialEq)]
+pub struct SpannedToken<'a> {
+    pub token: Token<'a>,
+    pub span: Span,
+    /// Whitespace and comments, and for the first token any byte order mark
+    /// or shebang line.
+    pub leading_trivia: &'a str,
+}
+
+/// A piece of the source from `merge_trivia`.
+#[derive(Debug, PartialEq)]
+pub enum Lossless<'a> {
+    Token(Token<'a>, Span),
+    /// A whole run of whitespace, however many comments it sits between.
+    Whitespace { text: &'a str, start: usize },
+    /// A single ordinary comment, or the byte order mark and shebang line
+    /// before the first token.
+    Comment { text: &'a str, start: usize },
+}
+
+/// A token that borrows its text from the source instead of owning it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TokenRef<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+}
+
+impl Token<'_> {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::EOF => TokenKind::EOF,
+            Token::Newline => TokenKind::Newline,
+            Token::Fn => TokenKind::Fn,
+            Token::Let => TokenKind::Let,
+            Token::Var => TokenKind::Var,
+            Token::Undefined => TokenKind::Undefined,
+            Token::If => TokenKind::If,
+            Token::Elif => TokenKind::Elif,
+            Token::Else => TokenKind::Else,
+            Token::Mod => TokenKind::Mod,
+            Token::Struct => TokenKind::Struct,
+            Token::Const => TokenKind::Const,
+            Token::Static => TokenKind::Static,
+            Token::Match => TokenKind::Match,
+            Token::Enum => TokenKind::Enum,
+            Token::While => TokenKind::While,
+            Token::As => TokenKind::As,
+            Token::Break => TokenKind::Break,
+            Token::For => TokenKind::For,
+            Token::Return => TokenKind::Return,
+            Token::Continue => TokenKind::Continue,
+            Token::Keyword(keyword) => Token::from(*keyword).kind(),
+            Token::CustomKeyword(_) => TokenKind::CustomKeyword,
+            Token::Op(_) => TokenKind::Op,
+            Token::CustomOp(_) => TokenKind::CustomOp,
+            Token::Paren(_) => TokenKind::Paren,
+            Token::IntLit { .. } | Token::IntValue { .. } => TokenKind::IntLit,
+            Token::FloatLit { .. } | Token::FloatValue { .. } => TokenKind::FloatLit,
+            Token::CharLit(_) => TokenKind::CharLit,
+            Token::BoolLit(_) => TokenKind::BoolLit,
+            Token::ByteLit(_) => TokenKind::ByteLit,
+            Token::ByteStrLit(_) => TokenKind::ByteStrLit,
+            Token::StrLit(_) | Token::LazyStrLit(_) => TokenKind::StrLit,
+            Token::Ident(_) | Token::InternedIdent(_) => TokenKind::Ident,
+            Token::Label(_) => TokenKind::Label,
+            Token::Placeholder(_) => TokenKind::Placeholder,
+            Token::DocComment(..) => TokenKind::DocComment,
+            Token::LineComment(_) => TokenKind::LineComment,
+            Token::BlockComment(_) => TokenKind::BlockComment,
+        }
+    }
+    /// The spelling of a keyword token.
+    pub fn keyword_str(&self) -> Option<&'static str> {
+        self.as_keyword().map(Keyword::as_str)
+    }
+    /// The keyword this token is, in either representation.
+    pub fn as_keyword(&self) -> Option<Keyword> {
+        Some(match self {
+            Token::Fn => Keyword::Fn,
+            Token::Let => Keyword::Let,
+            Token::Var => Keyword::Var,
+            Token::Undefined => Keyword::Undefined,
+            Token::If => Keyword::If,
+            Token::Elif => Keyword::Elif,
+            Token::Else => Keyword::Else,
+            Token::Mod => Keyword::Mod,
+            Token::Struct => Keyword::Struct,
+            Token::Const => Keyword::Const,
+            Token::Static => Keyword::Static,
+            Token::Match => Keyword::Match,
+            Token::Enum => Keyword::Enum,
+            Token::While => Keyword::While,
+            Token::As => Keyword::As,
+            Token::Break => Keyword::Break,
+            Token::For => Keyword::For,
+            Token::Return => Keyword::Return,
+            Token::Continue => Keyword::Continue,
+            Token::Keyword(keyword) => *keyword,
+            // Listed rather than `_` so a new variant has to be sorted here.
+            Token::EOF
+            | Token::Newline
+            | Token::CustomKeyword(_)
+            | Token::Op(_)
+            | Token::CustomOp(_)
+            | Token::Paren(_)
+            | Token::IntLit { .. }
+            | Token::FloatLit { .. }
+            | Token::IntValue { .. }
+            | Token::FloatValue { .. }
+            | Token::CharLit(_)
+            | Token::BoolLit(_)
+            | Token::StrLit(_)
+            | Token::ByteLit(_)
+            | Token::ByteStrLit(_)
+            | Token::LazyStrLit(_)
+            | Token::Ident(_)
+            | Token::InternedIdent(_)
+            | Token::Label(_)
+            | Token::Placeholder(_)
+            | Token::DocComment(..)
+            | Token::LineComment(_)
+            | Token::BlockComment(_) => return None,
+        })
+    }
+    /// Whether both tokens are the same variant, ignoring their payloads,
+    /// so any two `Ident`s are the same kind.
+    pub fn same_kind(&self, other: &Token) -> bool {
+        self.kind() == other.kind()
+    }
+}
+
+impl Display for Token<'_> {
+    /// Renders the token the way it is spelled in source, `EOF` as `<eof>`.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Token::EOF => write!(f, "<eof>"),
+            Token::Newline => writeln!(f),
+            Token::Op(op) => write!(f, "{}", op.as_str()),
+            Token::CustomOp(op) | Token::CustomKeyword(op) => write!(f, "{op}"),
+            Token::Paren(ch) => write!(f, "{ch}"),
+            Token::IntLit { digits, suffix } | Token::FloatLit { digits, suffix } => {
+                write!(f, "{digits}{}", suffix.unwrap_or(""))
+            }
+            Token::IntValue { value, suffix } => write!(f, "{value}{}", suffix.unwrap_or("")),
+            // `Debug` keeps the `.0` of whole numbers, so it reads back as a float.
+            Token::FloatValue { value, suffix } => write!(f, "{value:?}{}", suffix.unwrap_or("")),
+            Token::CharLit(ch) => write!(f, "'{}'", ch.escape_ascii()),
+            Token::BoolLit(b) => write!(f, "{b}"),
+            Token::StrLit(s) => {
+                write!(f, "\"")?;
+                write_str_contents(f, s)?;
+                write!(f, "\"")
+            }
+            Token::ByteLit(ch) => write!(f, "b'{}'", ch.escape_ascii()),
+            Token::ByteStrLit(s) => write!(f, "b\"{}\"", s.escape_ascii()),
+            Token::LazyStrLit(s) => write!(f, "\"{}\"", String::from_utf8_lossy(s.raw())),
+            Token::Ident(name) => write!(f, "{name}"),
+            Token::InternedIdent(symbol) => write!(f, "{symbol}"),
+            Token::Label(name) => write!(f, "'{name}"),
+            Token::Placeholder(count) => write!(f, "{}", "_".repeat(*count)),
+            Token::DocComment(style, form, text) => match (style, form) {
+                (DocStyle::Outer, DocForm::Line) => write!(f, "///{text}"),
+                (DocStyle::Inner, DocForm::Line) => write!(f, "//!{text}"),
+                (DocStyle::Outer, DocForm::Block) => write!(f, "/**{text}*/"),
+                (DocStyle::Inner, DocForm::Block) => write!(f, "/*!{text}*/"),
+            },
+            Token::LineComment(text) => write!(f, "//{text}"),
+            Token::BlockComment(text) => write!(f, "/*{text}*/"),
+            Token::Fn
+            | Token::Let
+            | Token::Var
+            | Token::Undefined
+            | Token::If
+            | Token::Elif
+            | Token::Else
+            | Token::Mod
+            | Token::Struct
+            | Token::Const
+            | Token::Static
+            | Token::Match
+            | Token::Enum
+            | Token::While
+            | Token::As
+            | Token::Break
+            | Token::For
+            | Token::Return
+            | Token::Continue
+            | Token::Keyword(_) => write!(f, "{}", self.keyword_str().unwrap_or_default()),
+        }
+    }
+}
+
+/// Writes the contents of a string literal so they lex back the same: text
+/// as it is, but with quotes, backslashes and ASCII control characters
+/// escaped. Bytes that aren't UTF-8 can't be written as they are and come
+/// out as `\xNN`, which doesn't lex back.
+fn write_str_contents(f: &mut core::fmt::Formatter<'_>, bytes: &[u8]) -> core::fmt::Result {
+    for chunk in bytes.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '"' | '\\' => write!(f, "\\{ch}")?,
+                _ if ch.is_ascii_control() => write!(f, "{}", (ch as u8).escape_ascii())?,
+                _ => write!(f, "{ch}")?,
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(f, "\\x{byte:02X}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Renders `tokens` joined by `sep` (usually a space), with the trailing
+/// `EOF` only when `include_eof` is set.
+pub fn display_stream(tokens: &[Token], sep: &str, include_eof: bool) -> String {
+    tokens
+        .iter()
+        .filter(|token| include_eof || **token != Token::EOF)
+        .map(Token::to_string)
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+/// The source bytes between the quotes of a string literal, escapes and all.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LazyStr(Vec<u8>);
+impl LazyStr {
+    pub fn raw(&self) -> &[u8] {
+        &self.0
+    }
+    pub fn decode(&self) -> Result<Vec<u8>, LexingError> {
+        let mut lexer = Lexer { src: &self.0, ..Lexer::new("") };
+        let mut ret = Vec::new();
+        while lexer.i < lexer.src.len() {
+            if lexer.skip_line_continuation() {
+                continue;
+            }
+            // Offsets into the literal alone would be misleading.
+            ret.push(lexer.parse_string_character().map_err(|err| LexingError { offset: None, ..err })?);
+        }
+        Ok(ret)
+    }
+}
+
+/// Outer doc comments (`///`, `/** */`) document the item that follows them,
+/// inner ones (`//!`, `/*! */`) document the item they are written inside of.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DocStyle {
+    Outer,
+    Inner,
+}
+/// Whether a doc comment was written as line comments (`///`, `//!`) or as a
+/// block comment (`/** */`, `/*! */`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DocForm {
+    Line,
+    Block,
+}
+
+/// Every keyword, for consumers that would rather match `Token::Keyword`
+/// than one variant per keyword.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Keyword {
+    Fn, Let, Var, Undefined, If, Elif, Else, Mod, Struct, Const, Static, Match, Enum, While, As, Break,
+    For, Return, Continue,
+}
+impl Keyword {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Keyword::Fn => "fn",
+            Keyword::Let => "let",
+            Keyword::Var => "var",
+            Keyword::Undefined => "undefined",
+            Keyword::If => "if",
+            Keyword::Elif => "elif",
+            Keyword::Else => "else",
+            Keyword::Mod => "mod",
+            Keyword::Struct => "struct",
+            Keyword::Const => "const",
+            Keyword::Static => "static",
+            Keyword::Match => "match",
+            Keyword::Enum => "enum",
+            Keyword::While => "while",
+            Keyword::As => "as",
+            Keyword::Break => "break",
+            Keyword::For => "for",
+            Keyword::Return => "return",
+            Keyword::Continue => "continue",
+        }
+    }
+}
+/// The per-keyword variant, which is what the parser expects.
+impl From<Keyword> for Token<'_> {
+    fn from(keyword: Keyword) -> Self {
+        match keyword {
+            Keyword::Fn => Token::Fn,
+            Keyword::Let => Token::Let,
+            Keyword::Var => Token::Var,
+            Keyword::Undefined => Token::Undefined,
+            Keyword::If => Token::If,
+            Keyword::Elif => Token::Elif,
+            Keyword::Else => Token::Else,
+            Keyword::Mod => Token::Mod,
+            Keyword::Struct => Token::Struct,
+            Keyword::Const => Token::Const,
+            Keyword::Static => Token::Static,
+            Keyword::Match => Token::Match,
+            Keyword::Enum => Token::Enum,
+            Keyword::While => Token::While,
+            Keyword::As => Token::As,
+            Keyword::Break => Token::Break,
+            Keyword::For => Token::For,
+            Keyword::Return => Token::Return,
+            Keyword::Continue => Token::Continue,
+        }
+    }
+}
 
-    Ident(String),
+/// The keyword token spelled by `s`, or `None` if `s` is an ordinary word.
+pub fn keyword_of_bytes(s: &[u8]) -> Option<Token<'static>> {
+    Some(match s {
+        b"fn" => Token::Fn,
+        b"let" => Token::Let,
+        b"var" => Token::Var,
+        b"undefined" => Token::Undefined,
+        b"if" => Token::If,
+        b"elif" => Token::Elif,
+        b"else" => Token::Else,
+        b"mod" => Token::Mod,
+        b"struct" => Token::Struct,
+        b"const" => Token::Const,
+        b"static" => Token::Static,
+        b"match" => Token::Match,
+        b"enum" => Token::Enum,
+        b"while" => Token::While,
+        b"as" => Token::As,
+        b"break" => Token::Break,
+        b"for" => Token::For,
+        b"return" => Token::Return,
+        b"continue" => Token::Continue,
+        b"true" => Token::BoolLit(true),
+        b"false" => Token::BoolLit(false),
+        _ => return None,
+    })
+}
+
+/// What went wrong, for callers that need to tell errors apart.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexErrorKind {
+    SourceTooLong { len: usize, max: usize },
+    TooManyTokens(usize),
+    IllegalChar(u8),
+    IllegalUnicodeChar(char),
+    UnclosedBracket { bracket: char, line: usize, col: usize },
+    /// A closing bracket that doesn't match the innermost open one, which
+    /// was opened at `line` and `col`.
+    MismatchedBracket { open: char, close: char, line: usize, col: usize },
+    UnmatchedBracket(char),
+    BadHexEscape,
+    HexEscapeOutOfRange(u8),
+    BadEscape(u8),
+    /// A literal cut off by the end of input inside an escape.
+    UnterminatedLiteral,
+    UnterminatedString,
+    UnterminatedRawString { hashes: usize },
+    UnterminatedChar,
+    UnterminatedBlockComment,
+    /// Text that has to be UTF-8, such as a comment, isn't.
+    InvalidUtf8,
+    EmptyChar,
+    NonAsciiChar(String),
+    OversizedChar(String),
+    NonAsciiByteString,
+    IntSuffixOnFloat { digits: String, suffix: String },
+    UnknownSuffix(String),
+    IntTooLarge(String),
+    ExponentHasNoDigits,
+    MisplacedSeparator,
+    InvalidDigit { digit: char, radix: u32 },
+    NoDigits { radix: u32 },
+    /// A lint from `Lexer::strict_ops`.
+    AdjacentOps { first: Op, second: Op, line: usize, col: usize, suggestion: &'static str },
+    MalformedLiteral { token: String, index: usize },
+    /// A bug in the lexer rather than in the source.
+    Internal(String),
+}
+impl Display for LexErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let radix_name = |radix: &u32| match radix {
+            16 => "Hexadecimal",
+            8 => "Octal",
+            _ => "Binary",
+        };
+        match self {
+            LexErrorKind::SourceTooLong { len, max } => write!(f, "Source Is {len} Bytes Long, Over The Limit Of {max}"),
+            LexErrorKind::TooManyTokens(max) => write!(f, "Source Has More Than {max} Tokens"),
+            LexErrorKind::IllegalChar(byte) => write!(f, "Illegal Character '{}' (0x{byte:02X})", byte.escape_ascii()),
+            LexErrorKind::IllegalUnicodeChar(ch) => write!(f, "Illegal Character '{ch}' (U+{:04X})", *ch as u32),
+            LexErrorKind::UnclosedBracket { bracket, line, col } => {
+                write!(f, "Unclosed '{bracket}' Opened At Line {line}, Column {col}")
+            }
+            LexErrorKind::MismatchedBracket { open, close, line, col } => write!(
+                f,
+                "Mismatched '{close}', Expected '{}' To Close '{open}' Opened At Line {line}, Column {col}",
+                closing_bracket(*open as u8) as char,
+            ),
+            LexErrorKind::UnmatchedBracket(bracket) => write!(f, "Unmatched '{bracket}'"),
+            LexErrorKind::BadHexEscape => write!(f, "Escape '\\x' Must Be Followed By Two Hex Digits"),
+            LexErrorKind::HexEscapeOutOfRange(value) => {
+                write!(f, "Hex Escape '\\x{value:02X}' Is Out Of Range, Must Be At Most '\\x7F'")
+            }
+            // `"C:\Users"` style paths are the usual way to get here.
+            LexErrorKind::BadEscape(ch) if ch.is_ascii_alphabetic() => {
+                write!(f, "Unknown Escape Sequence '\\{}', Use A Raw String r\"...\" For Paths", *ch as char)
+            }
+            LexErrorKind::BadEscape(ch) => write!(f, "Unknown Escape Sequence '\\{}'", ch.escape_ascii()),
+            LexErrorKind::UnterminatedLiteral => write!(f, "Literal Has No End"),
+            LexErrorKind::UnterminatedString => write!(f, "String Literal Has No End"),
+            LexErrorKind::UnterminatedRawString { hashes } => {
+                write!(f, "Raw String Literal Has No End, Expected '\"{}'", "#".repeat(*hashes))
+            }
+            LexErrorKind::UnterminatedChar => write!(f, "Unterminated Character Literal"),
+            LexErrorKind::UnterminatedBlockComment => write!(f, "Block Comment Has No End"),
+            LexErrorKind::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+            LexErrorKind::EmptyChar => write!(f, "Empty Character Literal"),
+            LexErrorKind::NonAsciiChar(content) => write!(f, "Character Literal '{content}' Is Not ASCII"),
+            LexErrorKind::OversizedChar(content) => write!(
+                f,
+                "Character Literal '{content}' Holds More Than One Character, Use A String \"{content}\" Instead",
+            ),
+            LexErrorKind::NonAsciiByteString => write!(f, "Byte String Literal Must Be ASCII"),
+            LexErrorKind::IntSuffixOnFloat { digits, suffix } => {
+                write!(f, "Integer Suffix '{suffix}' On Float Literal '{digits}'")
+            }
+            LexErrorKind::UnknownSuffix(suffix) => write!(f, "Unknown Numeric Suffix '{suffix}'"),
+            LexErrorKind::IntTooLarge(digits) => {
+                write!(f, "Integer Literal '{digits}' Is Too Large, Must Be At Most {}", u128::MAX)
+            }
+            LexErrorKind::ExponentHasNoDigits => write!(f, "Exponent Has No Digits"),
+            LexErrorKind::MisplacedSeparator => write!(f, "Digit Separator '_' Must Be Between Two Digits"),
+            LexErrorKind::InvalidDigit { digit, radix } => {
+                write!(f, "Invalid Digit '{digit}' In {} Literal", radix_name(radix))
+            }
+            LexErrorKind::NoDigits { radix } => write!(f, "{} Literal Has No Digits", radix_name(radix)),
+            LexErrorKind::AdjacentOps { first, second, line, col, suggestion } => write!(
+                f,
+                "Adjacent Operators '{}' And '{}' At Line {line}, Column {col}, Did You Mean '{suggestion}'?",
+                first.as_str(), second.as_str(),
+            ),
+            LexErrorKind::MalformedLiteral { token, index } => write!(f, "Malformed Literal {token} At Token {index}"),
+            LexErrorKind::Internal(message) => write!(f, "{message}"),
+        }
+    }
 }
 
 pub struct LexingError {
-    message: String,
+    kind: LexErrorKind,
+    /// Byte offset in the source the error points at, if it has one.
+    offset: Option<usize>,
+    /// Tab width of the lexer that failed, so rendered columns match its spans.
+    tab_width: usize,
+}
+impl LexingError {
+    pub fn kind(&self) -> &LexErrorKind {
+        &self.kind
+    }
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+    pub(crate) fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+    /// Renders the error rust-compiler style, with the offending line and a
+    /// caret under where it went wrong.
+    pub fn render(&self, source: &str) -> String {
+        self.render_snippet(source, None)
+    }
+    /// Like `render`, naming `filename` in the location line.
+    pub fn render_in(&self, filename: &str, source: &str) -> String {
+        self.render_snippet(source, Some(filename))
+    }
+    fn render_snippet(&self, source: &str, filename: Option<&str>) -> String {
+        let mut out = format!("error: {}\n", self.kind);
+        if let Some(offset) = self.offset {
+            super::error::write_snippet(&mut out, source, offset, self.tab_width, filename);
+        }
+        out
+    }
 }
 impl Error for LexingError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
@@ -42,309 +759,2438 @@ impl Error for LexingError {
     }
 }
 impl Display for LexingError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        match (&self.kind, self.offset) {
+            (LexErrorKind::IllegalChar(_) | LexErrorKind::IllegalUnicodeChar(_), Some(offset)) => {
+                write!(f, " At Offset {offset}")
+            }
+            _ => Ok(()),
+        }
     }
 }
 impl Debug for LexingError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "LexingError: {}", self.message)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "LexingError: {self}")
+    }
+}
+
+/// Lexer configuration that outlives a single source, for dialects that
+/// extend the built-in syntax.
+#[derive(Default)]
+pub struct LexerBuilder {
+    custom_ops: Vec<&'static str>,
+    keywords: BTreeMap<&'static [u8], Option<Token<'static>>>,
+}
+impl LexerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers an extra operator, lexed as `Token::CustomOp`. It competes
+    /// with the built-in operators by maximal munch, and loses ties.
+    pub fn custom_op(mut self, op: &'static str) -> Self {
+        self.custom_ops.push(op);
+        self
+    }
+    /// Lexes `word` as `token`, in place of the built-in keyword it may
+    /// be. A new keyword is usually `Token::CustomKeyword(word.into())`,
+    /// but any token will do, as in `keyword("func", Token::Fn)`.
+    pub fn keyword(mut self, word: &'static str, token: Token<'static>) -> Self {
+        self.keywords.insert(word.as_bytes(), Some(token));
+        self
+    }
+    /// Lexes the built-in keyword `word` as an ordinary identifier.
+    pub fn remove_keyword(mut self, word: &'static str) -> Self {
+        self.keywords.insert(word.as_bytes(), None);
+        self
+    }
+    pub fn build<'a>(&self, source: &'a str) -> Lexer<'a> {
+        let mut custom_ops = self.custom_ops.clone();
+        custom_ops.sort_by_key(|op| core::cmp::Reverse(op.len()));
+        Lexer { custom_ops, keywords: self.keywords.clone(), ..Lexer::new(source) }
+    }
+}
+
+/// Caps on how much work a `Lexer` does, for sources that can't be trusted.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LexerLimits {
+    pub max_bytes: usize,
+    /// Tokens other than `EOF`.
+    pub max_token_count: usize,
+}
+impl LexerLimits {
+    pub const UNLIMITED: LexerLimits = LexerLimits { max_bytes: usize::MAX, max_token_count: usize::MAX };
+}
+impl Default for LexerLimits {
+    fn default() -> Self {
+        Self::UNLIMITED
     }
 }
 
 pub struct Lexer<'a> {
     src: &'a [u8],
     i: usize,
+    /// 1-based position of `i`, kept up to date by `read_ch`.
+    line: usize,
+    col: usize,
+    /// Set by `from_str_unchecked`, whose caller guarantees the sentinel.
+    unchecked: bool,
+    check_brackets: bool,
+    /// Each still open bracket and its byte offset, when `check_brackets`
+    /// is on.
+    open_brackets: Vec<(u8, usize)>,
+    placeholders: bool,
+    lazy_strings: bool,
+    /// Longest first, so the first match is the longest one.
+    custom_ops: Vec<&'static str>,
+    /// Words lexed differently from the built-in keywords, `None` for an
+    /// identifier. Empty unless set through `LexerBuilder`.
+    keywords: BTreeMap<&'static [u8], Option<Token<'static>>>,
+    strict_ops: bool,
+    keyword_tokens: bool,
+    preserve_comments: bool,
+    parse_numbers: bool,
+    significant_newlines: bool,
+    tab_width: usize,
+    /// The last operator lexed, with its start and end offset, when
+    /// `strict_ops` is on.
+    last_op: Option<(Op, usize, usize)>,
+    /// How many `<` are open that a later `>` could close, as in
+    /// `Vec<Int>`, when `strict_ops` is on.
+    open_angles: usize,
+    /// Whether the last token was a `>` closing one of `open_angles`.
+    closed_angle: bool,
+    /// Whether the last token can end an operand, as an identifier, a
+    /// literal, `)` or `]` can, so that a `.` and digits after it are a
+    /// field access like `t.0` rather than a float.
+    after_operand: bool,
+    /// Whether the last token is a `.` right after an operand, so digits
+    /// after it are a field index and `t.0.1` isn't lexed with a `0.1`.
+    after_field_dot: bool,
+    lints: Vec<LexingError>,
+    /// Set once `next` has yielded `EOF` or an error.
+    finished: bool,
+    max_token_count: usize,
+    token_count: usize,
+    #[cfg(feature = "nfc")]
+    nfc_idents: bool,
 }
 
 impl<'a> Lexer<'a> {
+    /// The source may or may not end in a `\0` sentinel, both lex the same.
+    /// A leading UTF-8 byte order mark is skipped, and so is a `#!` line at
+    /// the very start, as in an executable script.
     pub fn new(source: &'a str) -> Self {
-        Self {
-            src: source.as_bytes(),
+        Self::from_bytes(source.as_bytes())
+    }
+    /// Like `new`, for a source that needn't be valid UTF-8. String and
+    /// character literals may hold invalid UTF-8, and so may comments that
+    /// are skipped, since their text is never looked at. In a comment that
+    /// becomes a token, a doc comment or one kept by `preserve_comments`, it
+    /// is an error, and anywhere else an illegal character.
+    pub fn from_bytes(source: &'a [u8]) -> Self {
+        let mut lexer = Self {
+            src: source.strip_suffix(b"\0").unwrap_or(source),
             i: 0,
+            line: 1,
+            col: 1,
+            unchecked: false,
+            check_brackets: false,
+            open_brackets: Vec::new(),
+            placeholders: false,
+            lazy_strings: false,
+            custom_ops: Vec::new(),
+            keywords: BTreeMap::new(),
+            strict_ops: false,
+            keyword_tokens: false,
+            preserve_comments: false,
+            parse_numbers: false,
+            significant_newlines: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            last_op: None,
+            open_angles: 0,
+            closed_angle: false,
+            after_operand: false,
+            after_field_dot: false,
+            lints: Vec::new(),
+            finished: false,
+            max_token_count: usize::MAX,
+            token_count: 0,
+            #[cfg(feature = "nfc")]
+            nfc_idents: false,
+        };
+        lexer.skip_preamble();
+        lexer
+    }
+    /// Fails right away if `source` is longer than `limits.max_bytes`, and
+    /// lexing fails once it would produce more than `limits.max_token_count`
+    /// tokens.
+    pub fn new_with_limits(source: &'a str, limits: LexerLimits) -> Result<Self, LexingError> {
+        if source.len() > limits.max_bytes {
+            return Err(LexingError {
+                kind: LexErrorKind::SourceTooLong { len: source.len(), max: limits.max_bytes },
+                offset: None,
+                tab_width: DEFAULT_TAB_WIDTH,
+            });
         }
+        Ok(Self { max_token_count: limits.max_token_count, ..Self::new(source) })
+    }
+    /// A lexer that reads the source without bounds checks.
+    ///
+    /// # Safety
+    /// `source` must end in the `\0` sentinel. The lexer stops at the first
+    /// `\0` it reaches and never reads past it, so with the sentinel in place
+    /// every read stays inside `source`.
+    pub unsafe fn from_str_unchecked(source: &'a str) -> Self {
+        debug_assert!(source.ends_with('\0'));
+        let mut lexer = Self {
+            src: source.as_bytes(),
+            unchecked: true,
+            ..Self::new("")
+        };
+        lexer.skip_preamble();
+        lexer
     }
-    pub fn lex(&mut self) -> Result<Vec<Token>, LexingError> {
-        let mut ret = vec![self.parse_token()?];
-        while *ret.last().unwrap() != Token::EOF {
-            ret.push(self.parse_token()?);
+    /// Skips a byte order mark, then a `#!` up to and including the end of
+    /// its line. Either one anywhere else is lexed like any other input, so
+    /// a later `#!` is `Op::HashBang`. So is a leading `#!` followed by a
+    /// `[`, as that starts an inner attribute like `#![attr]`.
+    fn skip_preamble(&mut self) {
+        // The mark takes up no column, so only the offset moves past it.
+        if self.starts_with(BOM) {
+            self.i += BOM.len();
+        }
+        if !self.starts_with(b"#!") {
+            return;
+        }
+        let rest = &self.src[self.i + 2..];
+        if rest.iter().find(|ch| !ch.is_ascii_whitespace()) == Some(&b'[') {
+            return;
+        }
+        while !self.at_end() {
+            let ends_line = is_line_break(self.src, self.i);
+            self.read_ch();
+            if ends_line {
+                break;
+            }
+        }
+    }
+    /// Checks that brackets are balanced and properly nested. A closing
+    /// bracket that doesn't match is an error pointing at it, and reaching
+    /// the end of input with an unclosed bracket is one that points at the
+    /// opening bracket rather than at the end of the file.
+    pub fn check_brackets(mut self, on: bool) -> Self {
+        self.check_brackets = on;
+        self
+    }
+    /// Lexes `__`, `___`, ... as `Token::Placeholder` instead of `Ident`.
+    /// A single `_` stays an `Ident` either way, the parser reads it as the
+    /// wildcard pattern.
+    pub fn placeholders(mut self, on: bool) -> Self {
+        self.placeholders = on;
+        self
+    }
+    /// Lexes string literals as `Token::LazyStrLit`, leaving their escapes
+    /// undecoded until `LazyStr::decode` is called.
+    pub fn lazy_strings(mut self, on: bool) -> Self {
+        self.lazy_strings = on;
+        self
+    }
+    /// Normalizes identifier text to NFC, so differently composed spellings
+    /// of the same identifier lex to equal `Ident`s.
+    #[cfg(feature = "nfc")]
+    pub fn nfc_idents(mut self, on: bool) -> Self {
+        self.nfc_idents = on;
+        self
+    }
+    /// Flags two assignment or comparison operators with nothing but
+    /// whitespace between them, like `a == = b`. These are lints, not
+    /// errors, and are collected in `take_lints`.
+    pub fn strict_ops(mut self, on: bool) -> Self {
+        self.strict_ops = on;
+        self
+    }
+    /// Lexes keywords as `Token::Keyword` instead of their own variants.
+    pub fn keyword_tokens(mut self, on: bool) -> Self {
+        self.keyword_tokens = on;
+        self
+    }
+    /// Lexes ordinary comments as `Token::LineComment` and
+    /// `Token::BlockComment` instead of skipping them, for tools that have
+    /// to keep them such as a formatter.
+    pub fn preserve_comments(mut self, on: bool) -> Self {
+        self.preserve_comments = on;
+        self
+    }
+    /// Lexes numeric literals as `Token::IntValue` and `Token::FloatValue`,
+    /// holding their parsed value. An integer that doesn't fit a `u128` is
+    /// then an error. Literals are never negative, a leading `-` is a
+    /// separate token.
+    pub fn parse_numbers(mut self, on: bool) -> Self {
+        self.parse_numbers = on;
+        self
+    }
+    /// Lexes line breaks as `Token::Newline` instead of skipping them, for
+    /// grammars that end statements at the end of a line. A run of line
+    /// breaks, with only whitespace and comments between them, is a single
+    /// `Newline`.
+    pub fn significant_newlines(mut self, on: bool) -> Self {
+        self.significant_newlines = on;
+        self
+    }
+    /// How many columns apart tab stops are, 4 unless set. Only columns
+    /// depend on it, never offsets.
+    pub fn tab_width(mut self, width: usize) -> Self {
+        self.tab_width = width.max(1);
+        self
+    }
+    /// The source a token was lexed from, escapes and all, or `None` if
+    /// `span` is out of range or, when lexing bytes, isn't UTF-8.
+    pub fn token_text(&self, span: Span) -> Option<&'a str> {
+        from_utf8(self.token_bytes(span)?).ok()
+    }
+    pub fn token_bytes(&self, span: Span) -> Option<&'a [u8]> {
+        self.src.get(span.start..span.end)
+    }
+    pub fn take_lints(&mut self) -> Vec<LexingError> {
+        core::mem::take(&mut self.lints)
+    }
+    pub fn lex(&mut self) -> Result<Vec<Token<'a>>, LexingError> {
+        self.collect()
+    }
+    /// Whether everything up to the end of the source has been consumed,
+    /// as it has once `EOF` is lexed.
+    pub fn at_eof(&self) -> bool {
+        self.at_end()
+    }
+    /// Lexes the rest of the source and checks that it is exactly
+    /// `expected`, leaving off the trailing `EOF`.
+    pub fn lex_exact(&mut self, expected: &[Token]) -> Result<bool, LexingError> {
+        let tokens = self.lex()?;
+        Ok(tokens.split_last().is_some_and(|(_, rest)| rest == expected))
+    }
+    /// Points the lexer at a new source, keeping its options and buffers,
+    /// as if it had been built for `source` to begin with.
+    pub fn reset(&mut self, source: &'a str) {
+        self.src = source.strip_suffix('\0').unwrap_or(source).as_bytes();
+        // Nothing vouches for a sentinel in the new source.
+        self.unchecked = false;
+        (self.i, self.line, self.col) = (0, 1, 1);
+        self.open_brackets.clear();
+        self.last_op = None;
+        (self.open_angles, self.closed_angle) = (0, false);
+        (self.after_operand, self.after_field_dot) = (false, false);
+        self.lints.clear();
+        self.finished = false;
+        self.token_count = 0;
+        self.skip_preamble();
+    }
+    /// Like `lex`, but reuses `buf`, which is cleared first. Together with
+    /// `reset` this re-lexes without allocating a new token buffer.
+    pub fn lex_into(&mut self, buf: &mut Vec<Token<'a>>) -> Result<(), LexingError> {
+        buf.clear();
+        for token in self {
+            buf.push(token?);
+        }
+        Ok(())
+    }
+    /// Like `lex`, but with every identifier interned. The interner comes
+    /// back with the tokens to resolve the symbols.
+    #[cfg(feature = "std")]
+    pub fn lex_interned(&mut self) -> Result<(Vec<Token<'a>>, Interner), LexingError> {
+        let mut interner = Interner::new();
+        let tokens = self
+            .map(|token| match token? {
+                Token::Ident(name) => Ok(Token::InternedIdent(interner.intern(&name))),
+                token => Ok(token),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((tokens, interner))
+    }
+    /// Like `lex`, but with the span of each token.
+    pub fn lex_spanned(&mut self) -> Result<Vec<(Token<'a>, Span)>, LexingError> {
+        let mut ret = Vec::new();
+        loop {
+            self.skip_ws()?;
+            let start = self.here();
+            let token = self.parse_token()?;
+            let is_eof = token == Token::EOF;
+            ret.push((token, self.span_from(start)));
+            if is_eof {
+                return Ok(ret);
+            }
+        }
+    }
+    /// Like `lex_spanned`, but carries on past an error from wherever the
+    /// failed token left off, at least one character further on. Every
+    /// error is returned, and the tokens always end in `EOF`. Only going
+    /// over `max_token_count` stops it early.
+    #[allow(clippy::type_complexity)]
+    pub fn lex_recovering(&mut self) -> (Vec<(Token<'a>, Span)>, Vec<LexingError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            let mut start = self.here();
+            let token = self.skip_ws().and_then(|()| {
+                start = self.here();
+                self.parse_token()
+            });
+            match token {
+                Ok(token) => {
+                    let is_eof = token == Token::EOF;
+                    tokens.push((token, self.span_from(start)));
+                    if is_eof {
+                        return (tokens, errors);
+                    }
+                }
+                Err(err) => {
+                    let stop = matches!(err.kind, LexErrorKind::TooManyTokens(_));
+                    errors.push(err);
+                    if stop || (self.i == start.0 && self.at_end()) {
+                        tokens.push((Token::EOF, self.span_from(self.here())));
+                        return (tokens, errors);
+                    }
+                    if self.i == start.0 {
+                        self.read_ch();
+                        while !self.at_end() && self.ch() & 0xC0 == 0x80 {
+                            self.read_ch();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    /// Like `lex`, but with the span of each token and of every run of
+    /// skipped trivia between them. Together they cover the whole source
+    /// past any byte order mark and shebang line.
+    #[allow(clippy::type_complexity)]
+    pub fn lex_diagnostics(&mut self) -> Result<(Vec<(Token<'a>, Span)>, Vec<TriviaSpan>), LexingError> {
+        let mut tokens = Vec::new();
+        let mut trivia = Vec::new();
+        loop {
+            let gap_start = self.here();
+            self.skip_ws()?;
+            if self.i > gap_start.0 {
+                trivia.push(TriviaSpan(self.span_from(gap_start)));
+            }
+            let start = self.here();
+            let token = self.parse_token()?;
+            let is_eof = token == Token::EOF;
+            tokens.push((token, self.span_from(start)));
+            if is_eof {
+                return Ok((tokens, trivia));
+            }
+        }
+    }
+    /// Like `lex_spanned`, but with the source text skipped before each
+    /// token. The trivia and token spans tile the source, so joining each
+    /// token's trivia and source text gives back the source byte for byte.
+    pub fn lex_with_trivia(&mut self) -> Result<Vec<SpannedToken<'a>>, LexingError> {
+        let mut ret = Vec::new();
+        let mut prev_end = 0;
+        loop {
+            self.skip_ws()?;
+            let leading_trivia = self.text(prev_end, self.i)?;
+            let start = self.here();
+            let token = self.parse_token()?;
+            let is_eof = token == Token::EOF;
+            prev_end = self.i;
+            ret.push(SpannedToken { token, span: self.span_from(start), leading_trivia });
+            if is_eof {
+                return Ok(ret);
+            }
+        }
+    }
+    /// Like `lex`, but each token only borrows its text from the source.
+    /// Identifiers and keywords are lexed without allocating, the text is
+    /// always the exact source slice, so a string literal that isn't UTF-8
+    /// is an error here.
+    pub fn lex_borrowed(&mut self) -> Result<Vec<TokenRef<'a>>, LexingError> {
+        let mut ret = Vec::new();
+        loop {
+            self.skip_ws()?;
+            let start = self.i;
+            let kind = match self.ch() {
+                b'r' if self.raw_string_hashes().is_some() => self.parse_token()?.kind(),
+                b'b' if self.at_byte_literal() => self.parse_token()?.kind(),
+                b'a'..=b'z' |
+                b'A'..=b'Z' | b'_'
+                => {
+                    let word = self.scan_ident_like();
+                    self.count_token(start)?;
+                    // Only the kind matters from here on, not the text.
+                    let token = self.word_token(word).unwrap_or(Token::Ident(Cow::Borrowed("")));
+                    self.note_token(&token);
+                    token.kind()
+                }
+                _ => self.parse_token()?.kind(),
+            };
+            ret.push(TokenRef {
+                kind,
+                text: self.text(start, self.i)?,
+            });
+            if kind == TokenKind::EOF {
+                return Ok(ret);
+            }
         }
-        Ok(ret)
     }
 
-    fn parse_token(&mut self) -> Result<Token, LexingError> {
-        self.skip_ws();
+    fn parse_token(&mut self) -> Result<Token<'a>, LexingError> {
+        self.skip_ws()?;
+        let start = self.i;
+        let token = self.scan_token()?;
+        if token != Token::EOF {
+            self.count_token(start)?;
+        }
+        if self.strict_ops {
+            self.check_adjacent_ops(&token, start);
+        }
+        self.note_token(&token);
+        Ok(token)
+    }
+    /// Records what `token` means for a `.` or digits right after it.
+    /// Comments in between don't change that.
+    fn note_token(&mut self, token: &Token) {
+        let kind = token.kind();
+        if matches!(kind, TokenKind::DocComment | TokenKind::LineComment | TokenKind::BlockComment) {
+            return;
+        }
+        self.after_field_dot = self.after_operand && *token == Token::Op(Op::Dot);
+        self.after_operand = matches!(token, Token::Paren(')' | ']'))
+            || matches!(
+                kind,
+                TokenKind::Ident | TokenKind::IntLit | TokenKind::FloatLit | TokenKind::CharLit | TokenKind::BoolLit
+                    | TokenKind::ByteLit | TokenKind::ByteStrLit | TokenKind::StrLit
+            );
+    }
+    fn scan_token(&mut self) -> Result<Token<'a>, LexingError> {
+        if let Some(token) = self.parse_custom_op() {
+            return Ok(token);
+        }
         match self.ch() {
+            b'r' if self.raw_string_hashes().is_some() => self.parse_raw_string_literal(),
+            b'b' if self.at_byte_literal() => self.parse_byte_literal(),
             b'a'..=b'z' |
             b'A'..=b'Z' | b'_'
-            => Ok(self.parse_ident_like()),
+            => self.parse_ident_like(),
+
+            0x80..=0xFF if self.unicode_char().is_some_and(char::is_alphabetic)
+            => self.parse_ident_like(),
 
-            b'0'..=b'9' => Ok(self.parse_numeric_literal()),
+            b'0'..=b'9' => self.parse_numeric_literal(),
 
             b'\"' => self.parse_string_literal(),
             b'\'' => self.parse_character_literal(),
 
             b'(' | b')' | b'[' | b']' | b'{' | b'}'
-            => Ok({
-                self.read_ch();
-                Token::Paren(self.src[self.i - 1] as char)
-            }),
+            => self.parse_paren(),
+
+            b'\n' | b'\r' if self.significant_newlines => self.parse_newline(),
 
             b'+' | b'-' | b'*' |
             b'/' | b'%' | b'!' |
             b':' | b'=' | b'&' |
             b'|' | b'~' | b'<' |
             b'>' | b'.' | b',' |
-            b'?' | b'$' | b'@'
+            b'?' | b'$' | b'@' |
+            b'^' | b';' | b'#'
             => self.parse_starts_with_sign(),
 
-            0 => Ok(Token::EOF),
+            // A NUL byte anywhere but the end is as illegal as any other.
+            0 if self.at_end() => self.parse_eof(),
 
-            _ => Err(LexingError { message: "Illegal Character".into() }),
+            other => Err(self.illegal_character(other)),
         }
     }
-    fn parse_ident_like(&mut self) -> Token {
-        let prev_i = self.i;
-        while self.ch().is_ascii_alphanumeric() || self.ch() == b'_' {
-            self.read_ch();
-        }
-        match &self.src[prev_i..self.i] {
-            b"fn" => Token::Fn,
-            b"let" => Token::Let,
-            b"var" => Token::Var,
-            b"undefined" => Token::Undefined,
-            b"if" => Token::If,
-            b"elif" => Token::Elif,
-            b"else" => Token::Else,
-            b"mod" => Token::Mod,
-            b"struct" => Token::Struct,
-            other => Token::Ident(from_utf8(other).unwrap().into())
+    /// An error for a state the lexer should never reach, in place of a
+    /// panic.
+    fn internal(&self, what: &str) -> LexingError {
+        self.error(LexErrorKind::Internal(format!("Internal Lexer Error: {what}")), self.i)
+    }
+    fn error(&self, kind: LexErrorKind, offset: usize) -> LexingError {
+        LexingError { kind, offset: Some(offset), tab_width: self.tab_width }
+    }
+    /// Counts one more token against `max_token_count`.
+    fn count_token(&mut self, start: usize) -> Result<(), LexingError> {
+        self.token_count += 1;
+        if self.token_count > self.max_token_count {
+            return Err(self.error(LexErrorKind::TooManyTokens(self.max_token_count), start));
         }
+        Ok(())
     }
-
-    fn parse_numeric_literal(&mut self) -> Token {
-        let prev_i = self.i;
-        while self.ch().is_ascii_digit() {
-            self.read_ch();
+    /// Names a non-ASCII character by its code point, anything else by its
+    /// byte.
+    fn illegal_character(&self, byte: u8) -> LexingError {
+        let kind = match self.unicode_char() {
+            Some(ch) => LexErrorKind::IllegalUnicodeChar(ch),
+            None => LexErrorKind::IllegalChar(byte),
+        };
+        self.error(kind, self.i)
+    }
+    /// Lexes the longest custom operator matching here, unless a built-in
+    /// operator at least as long matches too. Words and literals never start
+    /// a custom operator.
+    fn parse_custom_op(&mut self) -> Option<Token<'a>> {
+        if self.ch().is_ascii_alphanumeric() || matches!(self.ch(), b'_' | b'"' | b'\'') {
+            return None;
+        }
+        let op = *self.custom_ops.iter().find(|op| self.starts_with(op.as_bytes()))?;
+        let start = self.here();
+        let builtin = self.parse_starts_with_sign();
+        let builtin_len = self.i - start.0;
+        (self.i, self.line, self.col) = start;
+        if builtin.is_ok() && builtin_len >= op.len() {
+            return None;
         }
-        if self.ch() != b'.' || (self.ch() == b'.' && !self.src[self.i + 1].is_ascii_digit()) {
-            return Token::NumLit(from_utf8(&self.src[prev_i..self.i]).unwrap().into());
+        self.read_chs(op.len());
+        Some(Token::CustomOp(op.into()))
+    }
+    fn parse_paren(&mut self) -> Result<Token<'a>, LexingError> {
+        let ch = self.ch();
+        if self.check_brackets {
+            match (ch, self.open_brackets.last()) {
+                (b'(' | b'[' | b'{', _) => self.open_brackets.push((ch, self.i)),
+                (_, Some(&(open, _))) if closing_bracket(open) == ch => {
+                    self.open_brackets.pop();
+                }
+                (_, Some(&(open, offset))) => {
+                    let (line, col) = self.line_col(offset);
+                    return Err(self.error(
+                        LexErrorKind::MismatchedBracket { open: open as char, close: ch as char, line, col },
+                        self.i,
+                    ));
+                }
+                (_, None) => {
+                    return Err(self.error(LexErrorKind::UnmatchedBracket(ch as char), self.i));
+                }
+            }
         }
         self.read_ch();
-        while self.ch().is_ascii_digit() {
+        Ok(Token::Paren(ch as char))
+    }
+    fn parse_newline(&mut self) -> Result<Token<'a>, LexingError> {
+        while matches!(self.ch(), b'\n' | b'\r') {
             self.read_ch();
+            self.skip_ws()?;
         }
-        Token::NumLit(from_utf8(&self.src[prev_i..self.i]).unwrap().into())
+        Ok(Token::Newline)
     }
-    fn parse_string_literal(&mut self) -> Result<Token, LexingError> {
-        self.read_ch();
-        let mut ret = Vec::new();
+    fn parse_eof(&mut self) -> Result<Token<'a>, LexingError> {
+        match self.open_brackets.last() {
+            Some(&(bracket, offset)) => {
+                let (line, col) = self.line_col(offset);
+                Err(self.error(LexErrorKind::UnclosedBracket { bracket: bracket as char, line, col }, offset))
+            }
+            None => Ok(Token::EOF),
+        }
+    }
+    fn parse_ident_like(&mut self) -> Result<Token<'a>, LexingError> {
+        let start = self.i;
+        let word = self.scan_ident_like();
+        match self.word_token(word) {
+            Some(token) => Ok(token),
+            None => Ok(Token::Ident(self.ident_text(self.text(start, self.i)?))),
+        }
+    }
+    /// The token for an identifier-like word that doesn't lex as an `Ident`.
+    fn word_token(&self, word: &[u8]) -> Option<Token<'a>> {
+        if self.placeholders && word.len() > 1 && word.iter().all(|&ch| ch == b'_') {
+            return Some(Token::Placeholder(word.len()));
+        }
+        let token = match self.keywords.get(word) {
+            Some(token) => token.clone()?,
+            None => keyword_of_bytes(word)?,
+        };
+        Some(match token.as_keyword() {
+            Some(keyword) if self.keyword_tokens => Token::Keyword(keyword),
+            _ => token,
+        })
+    }
+    /// Identifiers may contain any alphabetic or numeric character, and
+    /// may start with any alphabetic one. ASCII never needs decoding.
+    fn scan_ident_like(&mut self) -> &'a [u8] {
+        let prev_i = self.i;
         loop {
-            if self.ch() == b'\"' {
+            if self.ch().is_ascii_alphanumeric() || self.ch() == b'_' {
+                self.read_ch();
+            } else if let Some(ch) = self.unicode_char().filter(|&ch| ch.is_alphanumeric() || is_combining_mark(ch)) {
+                self.read_chs(ch.len_utf8());
+            } else {
                 break;
             }
-            if self.ch() == b'\0' {
-                return Err(LexingError { message: "String Literal Has No End".into() });
-            }
-            ret.push(self.parse_string_character());
         }
-        Ok(Token::StrLit(ret))
+        &self.src[prev_i..self.i]
     }
-    fn parse_character_literal(&mut self) -> Result<Token, LexingError> {
-        self.read_ch();
-        let ret = Token::CharLit(self.parse_string_character());
-        self.read_ch();
-        if self.ch() != b'\'' {
-            Err(LexingError { message: "Invalid Character Literal".into() })
+    /// The non-ASCII character starting at the cursor, if there is one.
+    fn unicode_char(&self) -> Option<char> {
+        let len = match self.ch() {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return None,
+        };
+        from_utf8(self.src.get(self.i..self.i + len)?).ok()?.chars().next()
+    }
+    #[cfg(feature = "nfc")]
+    fn ident_text(&self, ident: &'a str) -> Cow<'a, str> {
+        use unicode_normalization::{is_nfc, UnicodeNormalization};
+        if self.nfc_idents && !is_nfc(ident) {
+            Cow::Owned(ident.nfc().collect())
         } else {
-            Ok(ret)
+            Cow::Borrowed(ident)
         }
     }
-    fn parse_string_character(&mut self) -> u8 {
-        todo!()
+    #[cfg(not(feature = "nfc"))]
+    fn ident_text(&self, ident: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(ident)
     }
 
-    fn parse_starts_with_sign(&mut self) -> Result<Token, LexingError> {
-        match self.ch() {
-            b'+' => self.parse_starts_with_plus(),
-            b'-' => self.parse_starts_with_minus(),
-            b'*' => self.parse_starts_with_star(),
-            b'/' => self.parse_starts_with_divide(),
-            b'%' => self.parse_starts_with_modulus(),
-            b'!' => self.parse_starts_with_exclamation_mark(),
-            b':' => self.parse_starts_with_colon(),
-            b'=' => self.parse_starts_with_equal(),
-            b'&' => self.parse_starts_with_ampersand(),
-            b'|' => self.parse_starts_with_pipe(),
-            b'~' => self.parse_starts_with_wavey(),
-            b'<' => self.parse_starts_with_smaller(),
-            b'>' => self.parse_starts_with_greater(),
-            b'.' => self.parse_starts_with_dot(),
-            b',' => Ok(Token::Sign(",")),
-            b'?' => Ok(Token::Sign("?")),
-            b'$' => Ok(Token::Sign("$")),
-            b'@' => Ok(Token::Sign("@")),
-            other => Err(LexingError {
-                message: format!("Unreachable character {other} was reached in function 'parse_starts_with_sign()'"),
-            }),
+    /// Parses the two hex digits of a `\xNN` escape. Only ASCII is allowed
+    /// so string literals stay valid UTF-8.
+    fn parse_hex_escape(&mut self) -> Result<u8, LexingError> {
+        let start = self.i - 2;
+        let digits = self.src.get(self.i..self.i + 2).filter(|digits| digits.iter().all(u8::is_ascii_hexdigit));
+        let Some(digits) = digits else {
+            return Err(self.error(LexErrorKind::BadHexEscape, start));
+        };
+        let value = digits.iter().fold(0, |acc, &digit| acc * 16 + (digit as char).to_digit(16).unwrap_or(0) as u8);
+        if !value.is_ascii() {
+            return Err(self.error(LexErrorKind::HexEscapeOutOfRange(value), start));
         }
+        self.read_chs(2);
+        Ok(value)
     }
-    fn parse_starts_with_plus(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"++" {
-            self.read_chs(2);
-            Token::Sign("++")
-        } else if self.chs(2) == b"+=" {
-            self.read_chs(2);
-            Token::Sign("+=")
-        } else {
+    fn parse_numeric_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        let prev_i = self.i;
+        if self.ch() == b'0' && matches!(self.peek(1), b'x' | b'o' | b'b') {
+            return self.parse_radix_literal();
+        }
+        self.scan_decimal_digits()?;
+        let has_fraction = self.ch() == b'.' && self.peek(1).is_ascii_digit() && !self.after_field_dot;
+        if has_fraction {
             self.read_ch();
-            Token::Sign("+")
-        })
+            self.scan_decimal_digits()?;
+        }
+        let has_exponent = matches!(self.ch(), b'e' | b'E') && !self.after_field_dot;
+        if has_exponent {
+            self.parse_exponent()?;
+        }
+        let digits = self.text(prev_i, self.i)?;
+        let suffix_start = self.i;
+        let suffix = self.parse_numeric_suffix()?;
+        let token = match suffix {
+            Some(suffix) if FLOAT_SUFFIXES.contains(&suffix) => Token::FloatLit { digits, suffix: Some(suffix) },
+            Some(suffix) if has_fraction || has_exponent => return Err(self.error(
+                LexErrorKind::IntSuffixOnFloat { digits: digits.into(), suffix: suffix.into() },
+                suffix_start,
+            )),
+            _ if has_fraction || has_exponent => Token::FloatLit { digits, suffix: None },
+            _ => Token::IntLit { digits, suffix },
+        };
+        self.numeric_value(token, prev_i)
     }
-    fn parse_starts_with_minus(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"--" {
-            self.read_chs(2);
-            Token::Sign("--")
-        } else if self.chs(2) == b"-=" {
-            self.read_chs(2);
-            Token::Sign("-=")
-        } else {
-            self.read_ch();
-            Token::Sign("-")
-        })
+    /// Swaps the literal's text for its value when `parse_numbers` is on.
+    fn numeric_value(&self, token: Token<'a>, start: usize) -> Result<Token<'a>, LexingError> {
+        if !self.parse_numbers {
+            return Ok(token);
+        }
+        match token {
+            Token::IntLit { digits, suffix } => {
+                let (radix, rest) = match digits.as_bytes() {
+                    [b'0', b'x', ..] => (16, &digits[2..]),
+                    [b'0', b'o', ..] => (8, &digits[2..]),
+                    [b'0', b'b', ..] => (2, &digits[2..]),
+                    _ => (10, digits),
+                };
+                // The digits were already checked, so overflow is the only way to fail.
+                match u128::from_str_radix(&rest.replace('_', ""), radix) {
+                    Ok(value) => Ok(Token::IntValue { value, suffix }),
+                    Err(_) => Err(self.error(LexErrorKind::IntTooLarge(digits.into()), start)),
+                }
+            }
+            Token::FloatLit { digits, suffix } => {
+                let value = digits.replace('_', "").parse().map_err(|_| self.internal("float literal didn't parse"))?;
+                Ok(Token::FloatValue { value, suffix })
+            }
+            token => Ok(token),
+        }
     }
-    fn parse_starts_with_star(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"*=" {
-            self.read_chs(2);
-            Token::Sign("*=")
-        } else {
+    /// The letters and digits right after a literal, without consuming them.
+    fn suffix_len(&self) -> usize {
+        if !self.ch().is_ascii_alphabetic() {
+            return 0;
+        }
+        self.src[self.i..].iter().take_while(|ch| ch.is_ascii_alphanumeric() || **ch == b'_').count()
+    }
+    /// Consumes a type suffix such as `u8` or `f64`. Any other word glued to
+    /// the literal is an error rather than an identifier.
+    fn parse_numeric_suffix(&mut self) -> Result<Option<&'a str>, LexingError> {
+        let len = self.suffix_len();
+        if len == 0 {
+            return Ok(None);
+        }
+        let suffix = self.text(self.i, self.i + len)?;
+        if !INT_SUFFIXES.contains(&suffix) && !FLOAT_SUFFIXES.contains(&suffix) {
+            return Err(self.error(LexErrorKind::UnknownSuffix(suffix.into()), self.i));
+        }
+        self.read_chs(len);
+        Ok(Some(suffix))
+    }
+    /// Consumes decimal digits, with single `_` separators allowed between
+    /// them. Separators are kept in the token text like the rest of the
+    /// literal.
+    fn scan_decimal_digits(&mut self) -> Result<(), LexingError> {
+        while self.ch().is_ascii_digit() || self.ch() == b'_' {
+            if self.ch() == b'_' && !self.peek(1).is_ascii_digit() {
+                return Err(self.misplaced_separator());
+            }
             self.read_ch();
-            Token::Sign("*")
-        })
+        }
+        Ok(())
     }
-    fn parse_starts_with_divide(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"/=" {
+    /// Consumes `e` or `E`, an optional sign and at least one digit.
+    fn parse_exponent(&mut self) -> Result<(), LexingError> {
+        let start = self.i;
+        self.read_ch();
+        if matches!(self.ch(), b'+' | b'-') {
+            self.read_ch();
+        }
+        if !self.ch().is_ascii_digit() {
+            return Err(self.error(LexErrorKind::ExponentHasNoDigits, start));
+        }
+        self.scan_decimal_digits()
+    }
+    fn misplaced_separator(&self) -> LexingError {
+        self.error(LexErrorKind::MisplacedSeparator, self.i)
+    }
+    /// Lexes `0x`, `0o` and `0b` integers, keeping the prefix in the token.
+    /// Any letter or digit right after the prefix belongs to the literal, so
+    /// `0b102` is an error rather than `0b10` followed by `2`.
+    fn parse_radix_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        let prev_i = self.i;
+        let radix = match self.peek(1) {
+            b'x' => 16,
+            b'o' => 8,
+            _ => 2,
+        };
+        self.read_chs(2);
+        let digits_start = self.i;
+        while self.ch().is_ascii_alphanumeric() || self.ch() == b'_' {
+            if self.ch() == b'_' {
+                if self.i == digits_start || !self.peek(1).is_ascii_alphanumeric() {
+                    return Err(self.misplaced_separator());
+                }
+            } else if !(self.ch() as char).is_digit(radix) {
+                // `0xFFu8`; a hex `f32` is digits, as in Rust.
+                let suffix = &self.src[self.i..self.i + self.suffix_len()];
+                if self.i > digits_start && INT_SUFFIXES.iter().any(|s| s.as_bytes() == suffix) {
+                    break;
+                }
+                return Err(self.error(LexErrorKind::InvalidDigit { digit: self.ch() as char, radix }, self.i));
+            }
+            self.read_ch();
+        }
+        if self.i == digits_start {
+            return Err(self.error(LexErrorKind::NoDigits { radix }, prev_i));
+        }
+        let digits = self.text(prev_i, self.i)?;
+        let token = Token::IntLit { digits, suffix: self.parse_numeric_suffix()? };
+        self.numeric_value(token, prev_i)
+    }
+    fn parse_string_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        if self.lazy_strings {
+            return self.parse_lazy_string_literal();
+        }
+        Ok(Token::StrLit(self.parse_string_bytes()?))
+    }
+    /// Decodes a string literal from its opening quote to past its closing
+    /// one.
+    fn parse_string_bytes(&mut self) -> Result<Vec<u8>, LexingError> {
+        self.read_ch();
+        let mut ret = Vec::new();
+        loop {
+            if self.ch() == b'\"' {
+                break;
+            }
+            if self.at_end() {
+                return Err(self.error(LexErrorKind::UnterminatedString, self.i));
+            }
+            if self.skip_line_continuation() {
+                continue;
+            }
+            ret.push(self.parse_string_character()?);
+        }
+        self.read_ch();
+        Ok(ret)
+    }
+    /// Skips a `\` right before a line break together with the line break,
+    /// so a string literal can go on on the next line. Only called between
+    /// characters, so the `\` can't be the second half of a `\\`.
+    fn skip_line_continuation(&mut self) -> bool {
+        let len = match (self.ch(), self.peek(1), self.peek(2)) {
+            (b'\\', b'\r', b'\n') => 3,
+            (b'\\', b'\n' | b'\r', _) => 2,
+            _ => return false,
+        };
+        self.read_chs(len);
+        true
+    }
+    /// `b"` or `b'`, except where the `'` starts a label as in `b'outer`.
+    fn at_byte_literal(&self) -> bool {
+        match self.peek(1) {
+            b'\"' => true,
+            b'\'' => !((self.peek(2).is_ascii_alphabetic() || self.peek(2) == b'_') && self.peek(3) != b'\''),
+            _ => false,
+        }
+    }
+    /// Byte literals share the escapes of text literals, which already keep
+    /// `\x` escapes ASCII, so only the content itself needs checking.
+    fn parse_byte_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        let start = self.i;
+        self.read_ch();
+        if self.ch() == b'\'' {
+            return match self.parse_character_literal()? {
+                Token::CharLit(ch) => Ok(Token::ByteLit(ch)),
+                _ => Err(self.internal("`at_byte_literal` let a label through")),
+            };
+        }
+        let bytes = self.parse_string_bytes()?;
+        if !bytes.is_ascii() {
+            return Err(self.error(LexErrorKind::NonAsciiByteString, start));
+        }
+        Ok(Token::ByteStrLit(bytes))
+    }
+    /// Finds the end of a string literal without decoding it, only stepping
+    /// over escapes so an escaped quote doesn't end the literal early.
+    fn parse_lazy_string_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        self.read_ch();
+        let prev_i = self.i;
+        loop {
+            match self.ch() {
+                _ if self.at_end() => {
+                    return Err(self.error(LexErrorKind::UnterminatedString, self.i));
+                }
+                b'\"' => break,
+                b'\\' => {
+                    self.read_ch();
+                    if !self.at_end() {
+                        self.read_ch();
+                    }
+                }
+                _ => self.read_ch(),
+            }
+        }
+        let ret = Token::LazyStrLit(LazyStr(self.src[prev_i..self.i].to_vec()));
+        self.read_ch();
+        Ok(ret)
+    }
+    /// The number of `#`s if a raw string `r"`, `r#"`, ... starts here.
+    fn raw_string_hashes(&self) -> Option<usize> {
+        let hashes = self.src.iter().skip(self.i + 1).take_while(|&&ch| ch == b'#').count();
+        (self.peek(1 + hashes) == b'"').then_some(hashes)
+    }
+    /// Reads a raw string verbatim, without escapes, up to a `"` followed by
+    /// as many `#`s as it was opened with.
+    fn parse_raw_string_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        let start = self.i;
+        let Some(hashes) = self.raw_string_hashes() else {
+            return Err(self.internal("raw string literal without its opening quote"));
+        };
+        self.read_chs(hashes + 2);
+        let content_start = self.i;
+        loop {
+            if self.ch() == b'"' && self.src.iter().skip(self.i + 1).take_while(|&&ch| ch == b'#').count() >= hashes {
+                break;
+            }
+            if self.at_end() {
+                return Err(self.error(LexErrorKind::UnterminatedRawString { hashes }, start));
+            }
+            self.read_ch();
+        }
+        let ret = Token::StrLit(self.src[content_start..self.i].to_vec());
+        self.read_chs(hashes + 1);
+        Ok(ret)
+    }
+    /// A `'` starts a label rather than a character literal when a word
+    /// follows that isn't a single character closed by another `'`.
+    fn parse_character_literal(&mut self) -> Result<Token<'a>, LexingError> {
+        let start = self.i;
+        self.read_ch();
+        if (self.ch().is_ascii_alphabetic() || self.ch() == b'_') && self.peek(1) != b'\'' {
+            let name_start = self.i;
+            self.scan_ident_like();
+            let name = self.text(name_start, self.i)?;
+            if self.ch() != b'\'' {
+                return Ok(Token::Label(self.ident_text(name)));
+            }
+            // `'ab'` starts out looking like a label.
+            return Err(self.oversized_character_literal(start));
+        }
+        match self.ch() {
+            _ if self.at_end() => return Err(self.unterminated_character_literal(start)),
+            b'\'' => return Err(self.error(LexErrorKind::EmptyChar, start)),
+            _ => {}
+        }
+        let ret = match self.parse_string_character() {
+            // A stray byte that isn't UTF-8, when lexing bytes.
+            Ok(ch) if !ch.is_ascii() => return Err(self.oversized_character_literal(start)),
+            Ok(ch) => Token::CharLit(ch),
+            Err(_) if self.at_end() => return Err(self.unterminated_character_literal(start)),
+            Err(err) => return Err(err),
+        };
+        if self.ch() != b'\'' {
+            return Err(self.oversized_character_literal(start));
+        }
+        self.read_ch();
+        Ok(ret)
+    }
+    fn unterminated_character_literal(&self, start: usize) -> LexingError {
+        self.error(LexErrorKind::UnterminatedChar, start)
+    }
+    /// Looks for the closing `'` on the same line to say what went wrong,
+    /// and leaves the literal unterminated if there is none.
+    fn oversized_character_literal(&self, start: usize) -> LexingError {
+        let rest = &self.src[start + 1..];
+        let Some(len) = rest.iter().position(|&ch| matches!(ch, b'\'' | b'\n' | b'\r' | b'\0')).filter(|&len| rest[len] == b'\'') else {
+            return self.unterminated_character_literal(start);
+        };
+        let content = String::from_utf8_lossy(&rest[..len]);
+        let kind = if content.chars().count() == 1 {
+            LexErrorKind::NonAsciiChar(content.into())
+        } else {
+            LexErrorKind::OversizedChar(content.into())
+        };
+        self.error(kind, start)
+    }
+    /// Consumes one possibly escaped character of a string or character
+    /// literal and returns the byte it stands for.
+    fn parse_string_character(&mut self) -> Result<u8, LexingError> {
+        let ch = self.ch();
+        if self.at_end() {
+            return Err(self.error(LexErrorKind::UnterminatedLiteral, self.i));
+        }
+        self.read_ch();
+        if ch != b'\\' {
+            return Ok(ch);
+        }
+        let escaped = self.ch();
+        if self.at_end() {
+            return Err(self.error(LexErrorKind::UnterminatedLiteral, self.i));
+        }
+        self.read_ch();
+        Ok(match escaped {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'0' => b'\0',
+            b'\\' | b'\"' | b'\'' => escaped,
+            b'x' => return self.parse_hex_escape(),
+            other => return Err(self.error(LexErrorKind::BadEscape(other), self.i - 2)),
+        })
+    }
+
+    /// Operators are lexed by maximal munch: each `parse_starts_with_*`
+    /// tries its longest spelling first, so the longest operator that is a
+    /// prefix of the remaining input always wins and whatever is left starts
+    /// the next token. `=>=` is `=>` then `=`, `>==` is `>=` then `=` and
+    /// `==>` is `==` then `>`; an operator is never split to make a later one
+    /// longer.
+    fn parse_starts_with_sign(&mut self) -> Result<Token<'a>, LexingError> {
+        match self.ch() {
+            b'+' => self.parse_starts_with_plus(),
+            b'-' => self.parse_starts_with_minus(),
+            b'*' => self.parse_starts_with_star(),
+            b'/' => self.parse_starts_with_divide(),
+            b'%' => self.parse_starts_with_modulus(),
+            b'!' => self.parse_starts_with_exclamation_mark(),
+            b':' => self.parse_starts_with_colon(),
+            b'=' => self.parse_starts_with_equal(),
+            b'&' => self.parse_starts_with_ampersand(),
+            b'|' => self.parse_starts_with_pipe(),
+            b'~' => self.parse_starts_with_wavey(),
+            b'^' => self.parse_starts_with_caret(),
+            b'<' => self.parse_starts_with_smaller(),
+            b'>' => self.parse_starts_with_greater(),
+            b'.' => self.parse_starts_with_dot(),
+            b'#' => self.parse_starts_with_hash(),
+            b',' => Ok({
+                self.read_ch();
+                Token::Op(Op::Comma)
+            }),
+            b';' => Ok({
+                self.read_ch();
+                Token::Op(Op::Semi)
+            }),
+            b'?' => Ok({
+                self.read_ch();
+                Token::Op(Op::Question)
+            }),
+            b'$' => Ok({
+                self.read_ch();
+                Token::Op(Op::Dollar)
+            }),
+            b'@' => Ok({
+                self.read_ch();
+                Token::Op(Op::At)
+            }),
+            other => Err(self.error(
+                LexErrorKind::Internal(format!(
+                    "Unreachable character {other} was reached in function 'parse_starts_with_sign()'"
+                )),
+                self.i,
+            )),
+        }
+    }
+    fn parse_starts_with_plus(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"++") {
+            self.read_chs(2);
+            Token::Op(Op::PlusPlus)
+        } else if self.starts_with(b"+=") {
+            self.read_chs(2);
+            Token::Op(Op::PlusEq)
+        } else {
+            self.read_ch();
+            Token::Op(Op::Plus)
+        })
+    }
+    fn parse_starts_with_minus(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"--") {
+            self.read_chs(2);
+            Token::Op(Op::MinusMinus)
+        } else if self.starts_with(b"-=") {
+            self.read_chs(2);
+            Token::Op(Op::MinusEq)
+        } else if self.starts_with(b"->") {
+            self.read_chs(2);
+            Token::Op(Op::Arrow)
+        } else {
+            self.read_ch();
+            Token::Op(Op::Minus)
+        })
+    }
+    fn parse_starts_with_star(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"**=") {
+            self.read_chs(3);
+            Token::Op(Op::StarStarEq)
+        } else if self.starts_with(b"**") {
+            self.read_chs(2);
+            Token::Op(Op::StarStar)
+        } else if self.starts_with(b"*=") {
+            self.read_chs(2);
+            Token::Op(Op::StarEq)
+        } else {
+            self.read_ch();
+            Token::Op(Op::Star)
+        })
+    }
+    fn parse_starts_with_divide(&mut self) -> Result<Token<'a>, LexingError> {
+        match self.doc_comment_start() {
+            Some((style, DocForm::Line)) => return self.parse_line_doc_comment(style),
+            Some((style, DocForm::Block)) => return self.parse_block_doc_comment(style),
+            None => {}
+        }
+        if self.starts_with(b"//") {
+            let start = self.i;
+            self.skip_line_comment();
+            return Ok(Token::LineComment(self.text(start + 2, self.i)?.into()));
+        }
+        if self.starts_with(b"/*") {
+            let start = self.i;
+            self.skip_block_comment()?;
+            return Ok(Token::BlockComment(self.text(start + 2, self.i - 2)?.into()));
+        }
+        Ok(if self.starts_with(b"/=") {
             self.read_chs(2);
-            Token::Sign("/=")
+            Token::Op(Op::SlashEq)
         } else {
             self.read_ch();
-            Token::Sign("/")
+            Token::Op(Op::Slash)
         })
     }
-    fn parse_starts_with_modulus(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"%=" {
+    fn parse_line_doc_comment(&mut self, style: DocStyle) -> Result<Token<'a>, LexingError> {
+        self.read_chs(3);
+        let prev_i = self.i;
+        while !matches!(self.ch(), b'\n' | b'\r') && !self.at_end() {
+            self.read_ch();
+        }
+        Ok(Token::DocComment(style, DocForm::Line, self.text(prev_i, self.i)?.into()))
+    }
+    fn parse_block_doc_comment(&mut self, style: DocStyle) -> Result<Token<'a>, LexingError> {
+        self.read_chs(3);
+        let prev_i = self.i;
+        while !self.starts_with(b"*/") {
+            if self.at_end() {
+                return Err(self.error(LexErrorKind::UnterminatedBlockComment, self.i));
+            }
+            self.read_ch();
+        }
+        let ret = Token::DocComment(style, DocForm::Block, self.text(prev_i, self.i)?.into());
+        self.read_chs(2);
+        Ok(ret)
+    }
+    fn parse_starts_with_modulus(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"%=") {
             self.read_chs(2);
-            Token::Sign("%=")
+            Token::Op(Op::PercentEq)
         } else {
             self.read_ch();
-            Token::Sign("%")
+            Token::Op(Op::Percent)
         })
     }
-    fn parse_starts_with_exclamation_mark(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"!=" {
+    fn parse_starts_with_exclamation_mark(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"!=") {
+            self.read_chs(2);
+            Token::Op(Op::BangEq)
+        } else {
+            self.read_ch();
+            Token::Op(Op::Bang)
+        })
+    }
+    fn parse_starts_with_colon(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b":=") {
+            self.read_chs(2);
+            Token::Op(Op::ColonEq)
+        } else if self.starts_with(b"::") {
             self.read_chs(2);
-            Token::Sign("!=")
+            Token::Op(Op::ColonColon)
         } else {
             self.read_ch();
-            Token::Sign("!")
+            Token::Op(Op::Colon)
         })
     }
-    fn parse_starts_with_colon(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b":=" {
+    fn parse_starts_with_equal(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"==") {
             self.read_chs(2);
-            Token::Sign(":=")
-        } else if self.chs(2) == b"::" {
+            Token::Op(Op::EqEq)
+        } else if self.starts_with(b"=>") {
             self.read_chs(2);
-            Token::Sign("::")
+            Token::Op(Op::FatArrow)
         } else {
-            Token::Sign(":")
+            self.read_ch();
+            Token::Op(Op::Eq)
         })
     }
-    fn parse_starts_with_equal(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"==" {
+    fn parse_starts_with_ampersand(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"&&") {
             self.read_chs(2);
-            Token::Sign("==")
-        } else if self.chs(2) == b"=>" {
+            Token::Op(Op::AmpAmp)
+        } else if self.starts_with(b"&=") {
             self.read_chs(2);
-            Token::Sign("=>")
+            Token::Op(Op::AmpEq)
         } else {
             self.read_ch();
-            Token::Sign("=")
+            Token::Op(Op::Amp)
         })
     }
-    fn parse_starts_with_ampersand(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"&&" {
+    fn parse_starts_with_pipe(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"||") {
+            self.read_chs(2);
+            Token::Op(Op::PipePipe)
+        } else if self.starts_with(b"|=") {
             self.read_chs(2);
-            Token::Sign("&&")
+            Token::Op(Op::PipeEq)
         } else {
             self.read_ch();
-            Token::Sign("&")
+            Token::Op(Op::Pipe)
         })
     }
-    fn parse_starts_with_pipe(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"||" {
+    fn parse_starts_with_wavey(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"~=") {
             self.read_chs(2);
-            Token::Sign("||")
+            Token::Op(Op::TildeEq)
         } else {
             self.read_ch();
-            Token::Sign("|")
+            Token::Op(Op::Tilde)
         })
     }
-    fn parse_starts_with_wavey(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"~=" {
+    fn parse_starts_with_caret(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"^=") {
             self.read_chs(2);
-            Token::Sign("~=")
+            Token::Op(Op::CaretEq)
         } else {
             self.read_ch();
-            Token::Sign("~")
+            Token::Op(Op::Caret)
         })
     }
-    fn parse_starts_with_smaller(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b"<=" {
+    fn parse_starts_with_smaller(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"<<=") {
+            self.read_chs(3);
+            Token::Op(Op::ShlEq)
+        } else if self.starts_with(b"<<") {
             self.read_chs(2);
-            Token::Sign("<=")
+            Token::Op(Op::Shl)
+        } else if self.starts_with(b"<=") {
+            self.read_chs(2);
+            Token::Op(Op::Le)
         } else {
             self.read_ch();
-            Token::Sign("<")
+            Token::Op(Op::Lt)
         })
     }
-    fn parse_starts_with_greater(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(2) == b">=" {
+    fn parse_starts_with_greater(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b">>=") {
+            self.read_chs(3);
+            Token::Op(Op::ShrEq)
+        } else if self.starts_with(b">>") {
+            self.read_chs(2);
+            Token::Op(Op::Shr)
+        } else if self.starts_with(b">=") {
             self.read_chs(2);
-            Token::Sign(">=")
+            Token::Op(Op::Ge)
         } else {
             self.read_ch();
-            Token::Sign(">")
+            Token::Op(Op::Gt)
         })
     }
-    fn parse_starts_with_dot(&mut self) -> Result<Token, LexingError> {
-        Ok(if self.chs(3) == b"..=" {
+    /// `.5` is a float, as in C, unless it follows an operand, as in `t.5`,
+    /// where it is a field access. `1..5` never gets here with `.5`, since
+    /// the `1` stops short of a `..` and the range is tried first.
+    fn parse_starts_with_dot(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"..=") {
             self.read_chs(3);
-            Token::Sign("..=")
-        } else if self.chs(2) == b".." {
+            Token::Op(Op::DotDotEq)
+        } else if self.starts_with(b"..") {
             self.read_chs(2);
-            Token::Sign("..")
+            Token::Op(Op::DotDot)
+        } else if self.peek(1).is_ascii_digit() && !self.after_operand {
+            return self.parse_numeric_literal();
         } else {
             self.read_ch();
-            Token::Sign(".")
+            Token::Op(Op::Dot)
+        })
+    }
+    /// `#!` at the very start of the source is a shebang and never gets
+    /// here unless a `[` follows; otherwise it starts an inner attribute, as
+    /// in `#![attr]`.
+    fn parse_starts_with_hash(&mut self) -> Result<Token<'a>, LexingError> {
+        Ok(if self.starts_with(b"#!") {
+            self.read_chs(2);
+            Token::Op(Op::HashBang)
+        } else {
+            self.read_ch();
+            Token::Op(Op::Hash)
         })
     }
 
-    fn skip_ws(&mut self) {
-        while self.ch().is_ascii_whitespace() {
+    /// Records a lint when `token` and the operator before it are both
+    /// assignment or comparison operators with only whitespace between.
+    fn check_adjacent_ops(&mut self, token: &Token, start: usize) {
+        let closed_angle = core::mem::replace(&mut self.closed_angle, false);
+        match token {
+            Token::Op(Op::Lt) => self.open_angles += 1,
+            Token::Op(Op::Gt) if self.open_angles > 0 => {
+                self.open_angles -= 1;
+                self.closed_angle = true;
+            }
+            Token::Op(Op::Shr) => self.open_angles = self.open_angles.saturating_sub(2),
+            // No type spans a statement or a block.
+            Token::Op(Op::Semi) | Token::Paren('{' | '}') => self.open_angles = 0,
+            _ => {}
+        }
+        let op = match token {
+            Token::Op(op) if is_assign_or_compare(*op) => *op,
+            _ => {
+                self.last_op = None;
+                return;
+            }
+        };
+        if let Some((prev, prev_start, prev_end)) = self.last_op {
+            // `>` then `=` is how `let v: Vec<Int> = x` closes a type.
+            let closes_generics = prev == Op::Gt && op == Op::Eq && closed_angle;
+            if !closes_generics && self.src[prev_end..start].iter().all(u8::is_ascii_whitespace) {
+                let joined = format!("{}{}", prev.as_str(), op.as_str());
+                let suggestion = match Lexer::new(&joined).lex().as_deref() {
+                    Ok([Token::Op(single), Token::EOF]) => single.as_str(),
+                    _ => prev.as_str(),
+                };
+                let (line, col) = self.line_col(prev_start);
+                self.lints.push(self.error(
+                    LexErrorKind::AdjacentOps { first: prev, second: op, line, col, suggestion },
+                    prev_start,
+                ));
+            }
+        }
+        self.last_op = Some((op, start, self.i));
+    }
+    /// 1-based line and column of a byte offset.
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let is_break = |&i: &usize| is_line_break(self.src, i);
+        let line_start = (0..offset).rev().find(is_break).map_or(0, |i| i + 1);
+        let col = self.src[line_start..offset].iter().fold(1, |col, &ch| self.next_col(col, ch));
+        ((0..offset).filter(is_break).count() + 1, col)
+    }
+    /// Skips whitespace and ordinary comments, which produce no token. Doc
+    /// comments, and ordinary ones with `preserve_comments` on, are left for
+    /// `parse_starts_with_divide`, and line breaks with
+    /// `significant_newlines` on for `parse_newline`.
+    fn skip_ws(&mut self) -> Result<(), LexingError> {
+        loop {
+            while self.ch().is_ascii_whitespace() && !(self.significant_newlines && matches!(self.ch(), b'\n' | b'\r')) {
+                self.read_ch();
+            }
+            if self.doc_comment_start().is_some() || self.preserve_comments {
+                return Ok(());
+            }
+            if self.starts_with(b"//") {
+                self.skip_line_comment();
+            } else if self.starts_with(b"/*") {
+                self.skip_block_comment()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+    /// Skips to the end of the line, leaving the line break.
+    fn skip_line_comment(&mut self) {
+        while !matches!(self.ch(), b'\n' | b'\r') && !self.at_end() {
             self.read_ch();
         }
     }
+    /// Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn skip_block_comment(&mut self) -> Result<(), LexingError> {
+        let start = self.i;
+        self.read_chs(2);
+        let mut depth = 1;
+        while depth > 0 {
+            if self.starts_with(b"/*") {
+                depth += 1;
+                self.read_chs(2);
+            } else if self.starts_with(b"*/") {
+                depth -= 1;
+                self.read_chs(2);
+            } else if self.at_end() {
+                return Err(self.error(LexErrorKind::UnterminatedBlockComment, start));
+            } else {
+                self.read_ch();
+            }
+        }
+        Ok(())
+    }
+    /// The style and form of the doc comment starting here. `////` and
+    /// `/***` are ordinary comments, as is `/**/`.
+    fn doc_comment_start(&self) -> Option<(DocStyle, DocForm)> {
+        if self.starts_with(b"//!") {
+            Some((DocStyle::Inner, DocForm::Line))
+        } else if self.starts_with(b"///") && !self.starts_with(b"////") {
+            Some((DocStyle::Outer, DocForm::Line))
+        } else if self.starts_with(b"/*!") {
+            Some((DocStyle::Inner, DocForm::Block))
+        } else if self.starts_with(b"/**") && !self.starts_with(b"/***") && !self.starts_with(b"/**/") {
+            Some((DocStyle::Outer, DocForm::Block))
+        } else {
+            None
+        }
+    }
     fn read_chs(&mut self, count: usize) {
-        self.i += count;
+        for _ in 0..count {
+            self.read_ch();
+        }
     }
     fn read_ch(&mut self) {
+        if is_line_break(self.src, self.i) {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col = self.next_col(self.col, self.ch());
+        }
         self.i += 1;
     }
-    fn chs(&self, count: usize) -> &'a [u8] {
-        &self.src[self.i..self.i+count]
+    /// The column after `ch`, for a `ch` that doesn't end the line.
+    fn next_col(&self, col: usize, ch: u8) -> usize {
+        next_col(col, ch, self.tab_width)
+    }
+    /// The offset, line and column of the cursor, for `span_from`.
+    fn here(&self) -> (usize, usize, usize) {
+        (self.i, self.line, self.col)
+    }
+    fn span_from(&self, (start, start_line, start_col): (usize, usize, usize)) -> Span {
+        Span { start, end: self.i, start_line, start_col, end_line: self.line, end_col: self.col }
     }
+    /// The source from `start` to `end` as text, for tokens that must be
+    /// UTF-8 even when lexing bytes.
+    fn text(&self, start: usize, end: usize) -> Result<&'a str, LexingError> {
+        from_utf8(&self.src[start..end])
+            .map_err(|err| self.error(LexErrorKind::InvalidUtf8, start + err.valid_up_to()))
+    }
+    /// Compares against exactly `s.len()` bytes, so a tail shorter than `s`
+    /// never matches and the caller falls through to the shorter operator.
+    fn starts_with(&self, s: &[u8]) -> bool {
+        self.src[self.i..].starts_with(s)
+    }
+    /// Whether the cursor is past the last byte of the source. Unlike
+    /// `ch() == 0` this is false on a NUL byte inside the source, which
+    /// literals may hold, except without bounds checks where the first NUL
+    /// has to be the sentinel.
+    fn at_end(&self) -> bool {
+        if self.unchecked {
+            self.ch() == b'\0'
+        } else {
+            self.i >= self.src.len()
+        }
+    }
+    /// Past the end of the source this reads as the `\0` sentinel.
     fn ch(&self) -> u8 {
-        self.src[self.i]
+        if self.unchecked {
+            debug_assert!(self.i < self.src.len());
+            // SAFETY: `from_str_unchecked` guarantees a trailing `\0`, and
+            // nothing steps the cursor over a `\0`.
+            return unsafe { *self.src.get_unchecked(self.i) };
+        }
+        self.src.get(self.i).copied().unwrap_or(0)
     }
-}
\ No newline at end of file
+    /// The byte `offset` past the cursor, `0` past the end like `ch`.
+    fn peek(&self, offset: usize) -> u8 {
+        self.src.get(self.i + offset).copied().unwrap_or(0)
+    }
+}
+
+/// Yields tokens up to and including `EOF`, or up to the first error, and
+/// `None` from then on.
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, LexingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let token = self.parse_token();
+        self.finished = matches!(token, Ok(Token::EOF) | Err(_));
+        Some(token)
+    }
+}
+
+/// Overview of a source file gathered in a single lexing pass.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Default)]
+pub struct LexStats {
+    /// Every token except the trailing `EOF`.
+    pub total_tokens: usize,
+    pub counts: HashMap<TokenKind, usize>,
+    pub lines: usize,
+    pub max_bracket_depth: usize,
+    /// Doc comments and ordinary ones. Only doc comments are tokens, so
+    /// ordinary comments are left out of `total_tokens` and `counts`.
+    pub comments: usize,
+}
+
+#[cfg(feature = "std")]
+pub fn analyze(source: &str) -> Result<LexStats, LexingError> {
+    let src = source.as_bytes();
+    // Counted like `str::lines`, but a lone `\r` ends a line too.
+    let breaks = (0..src.len()).filter(|&i| is_line_break(src, i)).count();
+    let unterminated = !src.is_empty() && !is_line_break(src, src.len() - 1);
+    let mut stats = LexStats {
+        lines: breaks + usize::from(unterminated),
+        ..Default::default()
+    };
+    let mut depth = 0usize;
+    for token in Lexer::new(source).preserve_comments(true).lex()? {
+        match token {
+            Token::EOF => continue,
+            Token::LineComment(_) | Token::BlockComment(_) => {
+                stats.comments += 1;
+                continue;
+            }
+            Token::Paren('(' | '[' | '{') => {
+                depth += 1;
+                stats.max_bracket_depth = stats.max_bracket_depth.max(depth);
+            }
+            Token::Paren(_) => depth = depth.saturating_sub(1),
+            Token::DocComment(..) => stats.comments += 1,
+            _ => {}
+        }
+        stats.total_tokens += 1;
+        *stats.counts.entry(token.kind()).or_default() += 1;
+    }
+    Ok(stats)
+}
+
+fn closing_bracket(open: u8) -> u8 {
+    match open {
+        b'(' => b')',
+        b'[' => b']',
+        _ => b'}',
+    }
+}
+
+/// How many columns apart tab stops are unless `Lexer::tab_width` says
+/// otherwise.
+pub(crate) const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// The column after `ch`, for a `ch` that doesn't end the line. Columns
+/// count bytes, and a tab goes to the next tab stop.
+pub(crate) fn next_col(col: usize, ch: u8, tab_width: usize) -> usize {
+    match ch {
+        b'\t' => (col - 1) / tab_width * tab_width + tab_width + 1,
+        _ => col + 1,
+    }
+}
+
+/// Whether the byte at `i` ends a line. `\n`, `\r\n` and a lone `\r` each
+/// count once; in `\r\n` it is the `\n` that does.
+fn is_line_break(src: &[u8], i: usize) -> bool {
+    match src.get(i) {
+        Some(b'\n') => true,
+        Some(b'\r') => src.get(i + 1) != Some(&b'\n'),
+        _ => false,
+    }
+}
+
+/// Drops every token matching `pred`, leaving the rest paired with their
+/// spans untouched.
+pub fn filter_out<S>(tokens: Vec<(Token, S)>, pred: impl Fn(&Token) -> bool) -> Vec<(Token, S)> {
+    tokens.into_iter().filter(|(token, _)| !pred(token)).collect()
+}
+
+/// Splits the trivia of `Lexer::lex_with_trivia` output into whitespace and
+/// comments, in source order with the tokens. Each run of whitespace is one
+/// piece, and each comment another, so joining the text of every piece
+/// gives back the source byte for byte.
+pub fn merge_trivia(tokens: Vec<SpannedToken>) -> Vec<Lossless> {
+    let mut ret = Vec::new();
+    for SpannedToken { token, span, leading_trivia } in tokens {
+        let bytes = leading_trivia.as_bytes();
+        let base = span.start - bytes.len();
+        let mut i = 0;
+        while i < bytes.len() {
+            let start = i;
+            let whitespace = bytes[i].is_ascii_whitespace();
+            if whitespace {
+                i += bytes[i..].iter().take_while(|ch| ch.is_ascii_whitespace()).count();
+            } else if bytes[i..].starts_with(b"/*") {
+                i = block_comment_end(bytes, i);
+            } else {
+                // A `//` comment, or a byte order mark and shebang line.
+                i += bytes[i..].iter().take_while(|ch| !matches!(ch, b'\n' | b'\r')).count();
+            }
+            let text = &leading_trivia[start..i];
+            ret.push(if whitespace {
+                Lossless::Whitespace { text, start: base + start }
+            } else {
+                Lossless::Comment { text, start: base + start }
+            });
+        }
+        ret.push(Lossless::Token(token, span));
+    }
+    ret
+}
+/// The offset just past the block comment starting at `start`, counting
+/// nested comments the way the lexer does.
+fn block_comment_end(bytes: &[u8], start: usize) -> usize {
+    let (mut i, mut depth) = (start + 2, 1);
+    while depth > 0 && i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") {
+            (i, depth) = (i + 2, depth + 1);
+        } else if bytes[i..].starts_with(b"*/") {
+            (i, depth) = (i + 2, depth - 1);
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
+
+/// Re-checks literal payloads in a token stream that didn't come straight
+/// from a `Lexer`, rejecting anything the lexer itself could not produce.
+pub fn validate_literals(tokens: &[Token]) -> Result<(), LexingError> {
+    for (i, token) in tokens.iter().enumerate() {
+        let valid = match token {
+            Token::IntLit { .. } | Token::FloatLit { .. } => lexes_as(&token.to_string(), token),
+            Token::IntValue { .. } => true,
+            Token::FloatValue { value, .. } => *value >= 0.0,
+            Token::CharLit(ch) => ch.is_ascii(),
+            // Any bytes at all, since `Lexer::from_bytes` keeps a literal's
+            // bytes whether or not they are UTF-8.
+            Token::StrLit(_) => true,
+            Token::ByteLit(ch) => ch.is_ascii(),
+            Token::ByteStrLit(bytes) => bytes.is_ascii(),
+            Token::LazyStrLit(lazy) => lazy.decode().is_ok(),
+            _ => true,
+        };
+        if !valid {
+            return Err(LexingError {
+                kind: LexErrorKind::MalformedLiteral { token: format!("{token:?}"), index: i },
+                offset: None,
+                tab_width: DEFAULT_TAB_WIDTH,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether `text` lexes to exactly `token` and nothing else.
+fn lexes_as(text: &str, token: &Token) -> bool {
+    Lexer::new(text).lex_exact(core::slice::from_ref(token)).unwrap_or(false)
+}
+
+const BOM: &[u8] = "\u{FEFF}".as_bytes();
+const INT_SUFFIXES: [&str; 10] = ["i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize"];
+const FLOAT_SUFFIXES: [&str; 2] = ["f32", "f64"];
+
+fn is_assign_or_compare(op: Op) -> bool {
+    matches!(
+        op,
+        Op::Eq | Op::ColonEq | Op::PlusEq | Op::MinusEq | Op::StarEq | Op::StarStarEq | Op::SlashEq | Op::PercentEq
+            | Op::TildeEq | Op::AmpEq | Op::PipeEq | Op::CaretEq | Op::ShlEq | Op::ShrEq
+            | Op::EqEq | Op::BangEq | Op::Le | Op::Lt | Op::Ge | Op::Gt
+    )
+}
+
+/// The combining diacritical mark blocks, so decomposed letters like
+/// `e\u{301}` stay one identifier.
+fn is_combining_mark(ch: char) -> bool {
+    matches!(ch, '\u{300}'..='\u{36F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The spelling of each token, without the trailing `EOF`.
+    fn spellings(source: &str) -> Vec<String> {
+        let mut tokens = Lexer::new(source).lex().unwrap();
+        assert_eq!(tokens.pop(), Some(Token::EOF));
+        tokens.iter().map(Token::to_string).collect()
+    }
+
+    #[test]
+    fn adjacent_operators_lex_by_maximal_munch() {
+        const OPS: [&str; 8] = ["=", "==", "=>", ">=", "->", "-", ">>=", "<<="];
+        // Every operator these can run into, for a greedy reference lexer.
+        const ALL: [&str; 15] = ["=", "==", "=>", ">", ">=", ">>", ">>=", "<", "<=", "<<", "<<=", "-", "--", "-=", "->"];
+        for a in OPS {
+            for b in OPS {
+                let source = format!("{a}{b}");
+                let mut expected = Vec::new();
+                let mut rest = source.as_str();
+                while !rest.is_empty() {
+                    let op = ALL.iter().filter(|op| rest.starts_with(*op)).max_by_key(|op| op.len()).unwrap();
+                    expected.push(op.to_string());
+                    rest = &rest[op.len()..];
+                }
+                assert_eq!(spellings(&source), expected, "lexing {source:?}");
+            }
+        }
+        assert_eq!(spellings("=>="), ["=>", "="]);
+        assert_eq!(spellings("->="), ["->", "="]);
+        assert_eq!(spellings(">=="), [">=", "="]);
+        assert_eq!(spellings("==>"), ["==", ">"]);
+    }
+
+    #[test]
+    fn fat_arrow_and_greater_equal_stay_distinct() {
+        assert_eq!(spellings("x => y"), ["x", "=>", "y"]);
+        assert_eq!(spellings("x >= y"), ["x", ">=", "y"]);
+        // Longest match first: `=>` wins, leaving `=`.
+        assert_eq!(
+            Lexer::new("x=>=y").lex().unwrap(),
+            [Token::Ident("x".into()), Token::Op(Op::FatArrow), Token::Op(Op::Eq), Token::Ident("y".into()), Token::EOF],
+        );
+    }
+
+    #[test]
+    fn operator_cut_short_by_end_of_input_falls_back() {
+        let x = || Token::Ident("x".into());
+        assert_eq!(Lexer::new("x..").lex().unwrap(), [x(), Token::Op(Op::DotDot), Token::EOF]);
+        assert_eq!(Lexer::new("x<").lex().unwrap(), [x(), Token::Op(Op::Lt), Token::EOF]);
+    }
+
+    #[test]
+    fn trailing_sentinel_is_optional() {
+        let source = "let x = a..=b; // done\n";
+        let with_sentinel = format!("{source}\0");
+        assert_eq!(Lexer::new(source).lex().unwrap(), Lexer::new(&with_sentinel).lex().unwrap());
+    }
+
+    fn lex_unchecked(source: &str) -> Result<Vec<Token<'_>>, LexingError> {
+        assert!(source.ends_with('\0'));
+        // SAFETY: checked just above.
+        unsafe { Lexer::from_str_unchecked(source) }.lex()
+    }
+
+    #[test]
+    fn unchecked_lexing_matches_checked() {
+        for source in ["", "fn main() { let x = 0x1F + 2.5e3; }", "#!/bin/sh\nx ..= 'c' \"s\\n\" /* c */ y", "a.. b<"] {
+            let terminated = format!("{source}\0");
+            assert_eq!(lex_unchecked(&terminated).unwrap(), Lexer::new(source).lex().unwrap(), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn source_without_sentinel_ends_in_eof() {
+        for source in ["x", "x = 12", "1.", "'a'", "a:"] {
+            let tokens = Lexer::new(source).lex().unwrap();
+            assert_eq!(tokens.last(), Some(&Token::EOF), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn lone_colon_advances() {
+        assert_eq!(
+            Lexer::new("a : b").lex().unwrap(),
+            [Token::Ident("a".into()), Token::Op(Op::Colon), Token::Ident("b".into()), Token::EOF]
+        );
+    }
+
+    #[test]
+    fn iterator_stays_done_after_eof() {
+        let mut lexer = Lexer::new("x");
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Ident("x".into()));
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::EOF);
+        for _ in 0..3 {
+            assert!(lexer.next().is_none());
+        }
+    }
+
+    #[test]
+    fn parsed_integers_check_their_range() {
+        let value = |source| Lexer::new(source).parse_numbers(true).lex().map(|tokens| tokens[0].clone());
+        assert_eq!(value("18446744073709551615").unwrap(), Token::IntValue { value: u64::MAX.into(), suffix: None });
+        assert_eq!(
+            value("340282366920938463463374607431768211455").unwrap(),
+            Token::IntValue { value: u128::MAX, suffix: None }
+        );
+        let err = value("340282366920938463463374607431768211456").unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::IntTooLarge(_)), "{err}");
+    }
+
+    #[test]
+    fn tokens_display_as_spelled() {
+        let tokens = Lexer::new("fn f(x) -> u8 { if x >= 0x1F { 'c' } else { \"s\\n\" } }").lex().unwrap();
+        assert_eq!(
+            display_stream(&tokens, " ", true),
+            "fn f ( x ) -> u8 { if x >= 0x1F { 'c' } else { \"s\\n\" } } <eof>"
+        );
+    }
+
+    #[test]
+    fn illegal_character_names_byte_and_offset() {
+        let message = |source: &str| Lexer::new(source).lex().unwrap_err().to_string();
+        assert_eq!(message("a `"), "Illegal Character '`' (0x60) At Offset 2");
+        assert_eq!(message("x = 1;\n  \u{7}"), "Illegal Character '\\x07' (0x07) At Offset 9");
+        assert_eq!(message("a\u{2192}"), "Illegal Character '\u{2192}' (U+2192) At Offset 1");
+    }
+
+    #[test]
+    fn nul_bytes_only_end_input_at_the_end() {
+        assert_eq!(Lexer::new("\"a\\0b\"").lex().unwrap(), [Token::StrLit(b"a\0b".to_vec()), Token::EOF]);
+        assert_eq!(Lexer::new("\"a\0b\"").lex().unwrap(), [Token::StrLit(b"a\0b".to_vec()), Token::EOF]);
+        assert_eq!(
+            Lexer::new(r#""he said \"hi\"""#).lex().unwrap(),
+            [Token::StrLit(br#"he said "hi""#.to_vec()), Token::EOF]
+        );
+        assert_eq!(Lexer::new("a // \0\n").lex().unwrap(), [Token::Ident("a".into()), Token::EOF]);
+        let err = Lexer::new("a \0 b").lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::IllegalChar(0)), "{err}");
+        assert_eq!(err.offset(), Some(2));
+    }
+
+    #[test]
+    fn fragments_are_consumed_exactly() {
+        let mut lexer = Lexer::new("x \0 y");
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Ident("x".into()));
+        assert!(!lexer.at_eof());
+        let range = [Token::Ident("a".into()), Token::Op(Op::DotDot), Token::Ident("b".into())];
+        assert!(Lexer::new("a..b").lex_exact(&range).unwrap());
+        assert!(!Lexer::new("a..b").lex_exact(&range[..2]).unwrap());
+        let mut lexer = Lexer::new("a");
+        lexer.lex().unwrap();
+        assert!(lexer.at_eof());
+    }
+
+    #[test]
+    fn hash_starts_attributes_but_not_shebangs() {
+        let attribute = |bang| {
+            let hash = Token::Op(if bang { Op::HashBang } else { Op::Hash });
+            [hash, Token::Paren('['), Token::Ident("foo".into()), Token::Paren(']'), Token::Fn, Token::EOF]
+        };
+        assert_eq!(Lexer::new("#[foo]\nfn").lex().unwrap(), attribute(false));
+        assert_eq!(Lexer::new("#![foo]\nfn").lex().unwrap(), attribute(true));
+        assert_eq!(Lexer::new("#! \n [foo]\nfn").lex().unwrap(), attribute(true));
+        assert_eq!(Lexer::new("#!/usr/bin/env lemon\nfn").lex().unwrap(), [Token::Fn, Token::EOF]);
+    }
+
+    #[test]
+    fn dot_digits_after_an_operand_is_a_field_access() {
+        assert_eq!(spellings("t.0"), ["t", ".", "0"]);
+        assert_eq!(spellings("t.0.1"), ["t", ".", "0", ".", "1"]);
+        assert_eq!(spellings("x.0.y"), ["x", ".", "0", ".", "y"]);
+        assert_eq!(spellings("x.0e1"), ["x", ".", "0", "e1"]);
+        assert_eq!(spellings("x = 0e1"), ["x", "=", "0e1"]);
+        assert_eq!(spellings("f().1 + a[0].2"), ["f", "(", ")", ".", "1", "+", "a", "[", "0", "]", ".", "2"]);
+        assert_eq!(spellings("x = .5 + (.25)"), ["x", "=", ".5", "+", "(", ".25", ")"]);
+        let borrowed = Lexer::new("t.0").lex_borrowed().unwrap();
+        assert_eq!(borrowed.iter().map(|token| token.text).collect::<Vec<_>>(), ["t", ".", "0", ""]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn custom_tokens_deserialize_borrowed() {
+        let lexer = LexerBuilder::new().custom_op("<=>").keyword("unless", Token::CustomKeyword("unless".into()));
+        let tokens = lexer.build("unless a <=> b").lex().unwrap();
+        let json = serde_json::to_string(&tokens).unwrap();
+        let back: Vec<Token> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, tokens);
+        assert!(matches!(&back[0], Token::CustomKeyword(Cow::Borrowed("unless"))));
+        assert!(matches!(&back[2], Token::CustomOp(Cow::Borrowed("<=>"))));
+    }
+
+    #[test]
+    fn display_lexes_back_to_the_same_tokens() {
+        let sources = [
+            "\"héllo wörld\" \"tab\\there\" \"say \\\"hi\\\"\" \"back\\\\slash\" \"nul\\0\\x7F\"",
+            "/// line doc\nfn f() {}",
+            "//! inner line doc\nx",
+            "/** one-line block doc */ fn g() {}",
+            "/*! inner block */ y",
+            "/**\n * multi\n */ z",
+        ];
+        for source in sources {
+            let tokens = Lexer::new(source).lex().unwrap();
+            let shown = display_stream(&tokens, "\n", false);
+            assert_eq!(Lexer::new(&shown).lex().unwrap(), tokens, "{shown}");
+        }
+        assert_eq!(Token::StrLit("é\"\n".into()).to_string(), "\"é\\\"\\n\"");
+    }
+
+    #[test]
+    fn strict_ops_flags_split_operators() {
+        let lints = |source| {
+            let mut lexer = Lexer::new(source).strict_ops(true);
+            lexer.lex().unwrap();
+            lexer.take_lints()
+        };
+        let found = lints("a == = b");
+        assert_eq!(found.len(), 1);
+        assert!(matches!(
+            found[0].kind(),
+            LexErrorKind::AdjacentOps { first: Op::EqEq, second: Op::Eq, line: 1, col: 3, suggestion: "==" }
+        ));
+        assert!(lints("a == b").is_empty());
+        assert!(lints("let x: Vec<Int> = y").is_empty());
+        assert!(lints("let x: Map<Int, Vec<Int>> = y").is_empty());
+        assert_eq!(lints("a > = b").len(), 1);
+        assert_eq!(lints("let x: Vec<Int> = a > = b").len(), 1);
+    }
+
+    #[test]
+    fn validate_literals_accepts_what_the_lexer_produces() {
+        let tokens = Lexer::from_bytes(b"f(1_000, 2.5e3, 'c', b\"ok\", \"\xff\xfe\")").lex().unwrap();
+        assert!(validate_literals(&tokens).is_ok());
+        let corrupted = [Token::Ident("x".into()), Token::IntLit { digits: "12a", suffix: None }];
+        let err = validate_literals(&corrupted).unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::MalformedLiteral { index: 1, .. }), "{err}");
+        assert!(validate_literals(&[Token::CharLit(0xE9)]).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn analyze_counts_a_small_program() {
+        let source = "/// Entry point.\nfn main() {\n    // Nothing yet.\n    f([1, 2]); /* done */\n}\n";
+        let stats = analyze(source).unwrap();
+        assert_eq!(stats.lines, 5);
+        assert_eq!(stats.comments, 3);
+        assert_eq!(stats.total_tokens, 16);
+        assert_eq!(stats.max_bracket_depth, 3);
+        assert_eq!(stats.counts[&TokenKind::Paren], 8);
+        assert_eq!(stats.counts[&TokenKind::DocComment], 1);
+        assert_eq!(stats.counts.get(&TokenKind::LineComment), None);
+    }
+
+    #[test]
+    fn borrowed_lexing_matches_owned() {
+        let source = "fn main() { let r = r#\"raw\"#; var b = b'x' + b\"s\";
+            if t.0 >= 3.14 { match x {} } else { undefined } }";
+        let owned = Lexer::new(source).lex().unwrap();
+        let borrowed = Lexer::new(source).lex_borrowed().unwrap();
+        assert!(owned.iter().map(Token::kind).eq(borrowed.iter().map(|token| token.kind)));
+    }
+
+    #[test]
+    fn builder_adds_changes_and_removes_keywords() {
+        let builder = LexerBuilder::new()
+            .keyword("yield", Token::CustomKeyword("yield".into()))
+            .keyword("func", Token::Fn)
+            .remove_keyword("mod");
+        assert_eq!(
+            builder.build("func gen() { yield mod }").lex().unwrap(),
+            [
+                Token::Fn,
+                Token::Ident("gen".into()),
+                Token::Paren('('),
+                Token::Paren(')'),
+                Token::Paren('{'),
+                Token::CustomKeyword("yield".into()),
+                Token::Ident("mod".into()),
+                Token::Paren('}'),
+                Token::EOF,
+            ]
+        );
+        assert_eq!(builder.build("yield").lex().unwrap()[0].to_string(), "yield");
+        // The built-in set is untouched for lexers not built from it.
+        assert_eq!(Lexer::new("yield mod").lex().unwrap()[1], Token::Mod);
+    }
+
+    #[test]
+    fn custom_operators_win_only_when_longer() {
+        let builder = LexerBuilder::new().custom_op("<|>").custom_op(":::").custom_op("<=");
+        let lexer = |source| builder.build(source).lex().unwrap();
+        let custom = |op: &'static str| Token::CustomOp(op.into());
+        let x = || Token::Ident("x".into());
+        // Longer than the built-in `<` and `::` they start with.
+        assert_eq!(lexer("x<|>x"), [x(), custom("<|>"), x(), Token::EOF]);
+        assert_eq!(lexer("x:::x"), [x(), custom(":::"), x(), Token::EOF]);
+        // A tie goes to the built-in, and a longer built-in wins outright.
+        assert_eq!(lexer("x<=x"), [x(), Token::Op(Op::Le), x(), Token::EOF]);
+        assert_eq!(lexer("x<<=x"), [x(), Token::Op(Op::ShlEq), x(), Token::EOF]);
+        // Falls back to built-ins when the custom operator doesn't match.
+        assert_eq!(lexer("x<|x"), [x(), Token::Op(Op::Lt), Token::Op(Op::Pipe), x(), Token::EOF]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_only_checked_where_it_becomes_text() {
+        let x = Token::Ident("x".into());
+        assert_eq!(Lexer::from_bytes(b"x // \xff\n/* \xfe */").lex().unwrap(), [x, Token::EOF]);
+        let err = Lexer::from_bytes(b"/// \xff").lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::InvalidUtf8), "{err}");
+        let err = Lexer::from_bytes(b"// \xff").preserve_comments(true).lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::InvalidUtf8), "{err}");
+        let err = Lexer::from_bytes(b"x \xff").lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::IllegalChar(0xFF)), "{err}");
+    }
+
+    #[test]
+    fn lex_recovering_carries_on_past_errors() {
+        let (tokens, errors) = Lexer::new("a ` b \u{2192} c").lex_recovering();
+        let starts: Vec<_> = tokens.iter().map(|(token, span)| (token.clone(), span.start)).collect();
+        let ident = |name: &'static str| Token::Ident(name.into());
+        assert_eq!(starts, [(ident("a"), 0), (ident("b"), 4), (ident("c"), 10), (Token::EOF, 11)]);
+        assert_eq!(errors.iter().map(LexingError::offset).collect::<Vec<_>>(), [Some(2), Some(6)]);
+        let (tokens, errors) = Lexer::new("x \"open").lex_recovering();
+        assert_eq!(tokens.len(), 2);
+        assert!(matches!(errors[..], [LexingError { kind: LexErrorKind::UnterminatedString, .. }]), "{errors:?}");
+    }
+
+    #[test]
+    fn every_keyword_displays_as_its_text() {
+        let words = "fn let var undefined if elif else mod struct const static match enum while as break for return continue";
+        for word in words.split(' ').chain(["true", "false"]) {
+            for mut lexer in [Lexer::new(word), Lexer::new(word).keyword_tokens(true)] {
+                let tokens = lexer.lex().unwrap();
+                assert!(!matches!(tokens[0], Token::Ident(_)), "{word}");
+                assert_eq!(display_stream(&tokens, " ", false), word);
+            }
+        }
+    }
+
+    #[test]
+    fn merged_trivia_keeps_comments_apart_and_every_byte() {
+        let source = "\u{FEFF}#!/bin/lemon\nfn f() {  \n\t// one\n  /* two /* nested */ */ x }\n";
+        let mut rebuilt = String::new();
+        let mut trivia = Vec::new();
+        for piece in merge_trivia(Lexer::new(source).lex_with_trivia().unwrap()) {
+            let (text, start) = match piece {
+                Lossless::Token(_, span) => (&source[span.start..span.end], span.start),
+                Lossless::Whitespace { text, start } => {
+                    trivia.push(("ws", text));
+                    (text, start)
+                }
+                Lossless::Comment { text, start } => {
+                    trivia.push(("comment", text));
+                    (text, start)
+                }
+            };
+            assert_eq!(start, rebuilt.len());
+            rebuilt.push_str(text);
+        }
+        assert_eq!(rebuilt, source);
+        assert_eq!(
+            trivia,
+            [
+                ("comment", "\u{FEFF}#!/bin/lemon"),
+                ("ws", "\n"),
+                ("ws", " "),
+                ("ws", " "),
+                ("ws", "  \n\t"),
+                ("comment", "// one"),
+                ("ws", "\n  "),
+                ("comment", "/* two /* nested */ */"),
+                ("ws", " "),
+                ("ws", " "),
+                ("ws", "\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn doc_comments_carry_their_style() {
+        let doc = |source: &str| match Lexer::new(source).lex().unwrap().as_slice() {
+            [Token::DocComment(style, form, text), Token::EOF] => (*style, *form, text.clone()),
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(doc("/// outer"), (DocStyle::Outer, DocForm::Line, " outer".into()));
+        assert_eq!(doc("//! inner"), (DocStyle::Inner, DocForm::Line, " inner".into()));
+        assert_eq!(doc("/** outer */"), (DocStyle::Outer, DocForm::Block, " outer ".into()));
+        assert_eq!(doc("/*! inner */"), (DocStyle::Inner, DocForm::Block, " inner ".into()));
+        assert_eq!(Lexer::new("//// plain\n/*** plain */ /**/").lex().unwrap(), [Token::EOF]);
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn nfc_idents_make_composed_and_decomposed_equal() {
+        let (composed, decomposed) = ("caf\u{E9}", "cafe\u{301}");
+        let lex = |source: &'static str, on: bool| Lexer::new(source).nfc_idents(on).lex().unwrap();
+        assert_eq!(lex(composed, true), lex(decomposed, true));
+        assert_eq!(lex(decomposed, true)[0], Token::Ident(composed.into()));
+        assert_ne!(lex(composed, false), lex(decomposed, false));
+    }
+
+    #[test]
+    fn keyword_of_bytes_covers_every_keyword() {
+        let keywords = [
+            Keyword::Fn, Keyword::Let, Keyword::Var, Keyword::Undefined, Keyword::If, Keyword::Elif, Keyword::Else,
+            Keyword::Mod, Keyword::Struct, Keyword::Const, Keyword::Static, Keyword::Match, Keyword::Enum,
+            Keyword::While, Keyword::As, Keyword::Break, Keyword::For, Keyword::Return, Keyword::Continue,
+        ];
+        for keyword in keywords {
+            assert_eq!(keyword_of_bytes(keyword.as_str().as_bytes()), Some(Token::from(keyword)));
+        }
+        assert_eq!(keyword_of_bytes(b"true"), Some(Token::BoolLit(true)));
+        assert_eq!(keyword_of_bytes(b"false"), Some(Token::BoolLit(false)));
+        for word in [&b"foo"[..], b"Fn", b"iff", b"els", b"", b"fn "] {
+            assert_eq!(keyword_of_bytes(word), None);
+        }
+    }
+
+    #[test]
+    fn filter_out_drops_comments_and_keeps_spans() {
+        let source = "/// doc\nx // note\n/* block */ y";
+        let tokens = Lexer::new(source).preserve_comments(true).lex_spanned().unwrap();
+        let is_comment = |token: &Token| {
+            matches!(token, Token::DocComment(..) | Token::LineComment(_) | Token::BlockComment(_))
+        };
+        let kept = filter_out(tokens, is_comment);
+        let texts: Vec<_> = kept.iter().map(|(_, span)| &source[span.start..span.end]).collect();
+        assert_eq!(texts, ["x", "y", ""]);
+        assert_eq!(kept[1].1.start_line, 3);
+    }
+
+    #[test]
+    fn placeholders_count_their_underscores() {
+        let lex = |source| Lexer::new(source).placeholders(true).lex().unwrap();
+        assert_eq!(lex("_"), [Token::Ident("_".into()), Token::EOF]);
+        assert_eq!(lex("__"), [Token::Placeholder(2), Token::EOF]);
+        assert_eq!(lex("___"), [Token::Placeholder(3), Token::EOF]);
+        assert_eq!(lex("__x"), [Token::Ident("__x".into()), Token::EOF]);
+        assert_eq!(Lexer::new("__").lex().unwrap(), [Token::Ident("__".into()), Token::EOF]);
+    }
+
+    #[test]
+    fn every_operator_lexes_to_its_op() {
+        let ops = [
+            Op::PlusPlus, Op::PlusEq, Op::Plus, Op::MinusMinus, Op::MinusEq, Op::Arrow, Op::Minus,
+            Op::StarStarEq, Op::StarStar, Op::StarEq, Op::Star, Op::SlashEq, Op::Slash, Op::PercentEq, Op::Percent,
+            Op::BangEq, Op::Bang, Op::ColonEq, Op::ColonColon, Op::Colon, Op::EqEq, Op::FatArrow, Op::Eq,
+            Op::AmpAmp, Op::AmpEq, Op::Amp, Op::PipePipe, Op::PipeEq, Op::Pipe, Op::TildeEq, Op::Tilde,
+            Op::CaretEq, Op::Caret, Op::ShlEq, Op::Shl, Op::Le, Op::Lt, Op::ShrEq, Op::Shr, Op::Ge, Op::Gt,
+            Op::DotDotEq, Op::DotDot, Op::Dot, Op::HashBang, Op::Hash,
+            Op::Comma, Op::Semi, Op::Question, Op::Dollar, Op::At,
+        ];
+        for op in ops {
+            let text: &'static str = op.into();
+            assert_eq!(text, op.as_str());
+            // After `x`, so `#!` isn't taken for a shebang.
+            let source = format!("x {text}");
+            assert_eq!(Lexer::new(&source).lex().unwrap(), [Token::Ident("x".into()), Token::Op(op), Token::EOF]);
+        }
+        assert_eq!(<&str>::from(Op::Arrow), "->");
+        assert_eq!(<&str>::from(Op::StarStarEq), "**=");
+    }
+
+    #[test]
+    fn lazy_strings_keep_escapes_until_decoded() {
+        let tokens = Lexer::new(r#""a\nb""#).lazy_strings(true).lex().unwrap();
+        let [Token::LazyStrLit(lazy), Token::EOF] = tokens.as_slice() else { panic!("{tokens:?}") };
+        assert_eq!(lazy.raw(), br"a\nb");
+        assert_eq!(lazy.decode().unwrap(), b"a\nb");
+        let tokens = Lexer::new(r#""\q""#).lazy_strings(true).lex().unwrap();
+        let [Token::LazyStrLit(bad), Token::EOF] = tokens.as_slice() else { panic!("{tokens:?}") };
+        assert_eq!(bad.decode().unwrap_err().offset(), None);
+    }
+
+    #[test]
+    fn unclosed_brace_points_at_its_opener() {
+        let source = "fn f() {\n    if x { y }\n";
+        let err = Lexer::new(source).check_brackets(true).lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::UnclosedBracket { bracket: '{', line: 1, col: 8 }), "{err}");
+        assert_eq!(err.offset(), Some(7));
+        assert_eq!(err.to_string(), "Unclosed '{' Opened At Line 1, Column 8");
+        assert!(Lexer::new("fn f() { if x { y } }").check_brackets(true).lex().is_ok());
+    }
+
+    #[test]
+    fn same_kind_ignores_payloads() {
+        let ident = |name: &'static str| Token::Ident(name.into());
+        assert!(ident("a").same_kind(&ident("b")));
+        assert!(ident("a").same_kind(&Token::Ident(String::new().into())));
+        assert!(!ident("fn").same_kind(&Token::Fn));
+        assert!(Token::Op(Op::Plus).same_kind(&Token::Op(Op::Minus)));
+        assert_eq!(ident("a").kind(), TokenKind::Ident);
+        assert_ne!(ident("a").kind(), Token::Fn.kind());
+    }
+
+    #[test]
+    fn diagnostics_spans_tile_the_source() {
+        let source = "  fn f() { // note\n\t/* a */ x }\n";
+        let (tokens, trivia) = Lexer::new(source).lex_diagnostics().unwrap();
+        let mut spans: Vec<Span> = tokens.iter().map(|(_, span)| *span).chain(trivia.iter().map(|t| t.0)).collect();
+        spans.sort_by_key(|span| (span.start, span.end));
+        let mut end = 0;
+        for span in spans.iter().filter(|span| span.start < span.end) {
+            assert_eq!(span.start, end, "{spans:?}");
+            end = span.end;
+        }
+        assert_eq!(end, source.len());
+        assert_eq!(trivia.len(), 6);
+        assert_eq!(&source[trivia[3].0.start..trivia[3].0.end], " // note\n\t/* a */ ");
+    }
+
+    #[test]
+    fn path_like_escapes_suggest_a_raw_string() {
+        let err = Lexer::new(r#"p = "C:\Users""#).lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::BadEscape(b'U')), "{err}");
+        assert_eq!(err.offset(), Some(7));
+        assert!(err.to_string().contains("Use A Raw String r\"...\""), "{err}");
+        assert_eq!(Lexer::new(r#"r"C:\Users""#).lex().unwrap(), [Token::StrLit(br"C:\Users".to_vec()), Token::EOF]);
+        let other = Lexer::new(r#""\%""#).lex().unwrap_err();
+        assert!(!other.to_string().contains("Raw String"), "{other}");
+    }
+
+    #[test]
+    fn keyword_tokens_round_trip_through_keyword() {
+        let tokens = Lexer::new("fn f").keyword_tokens(true).lex().unwrap();
+        assert_eq!(tokens, [Token::Keyword(Keyword::Fn), Token::Ident("f".into()), Token::EOF]);
+        assert_eq!(Token::from(Keyword::Fn), Token::Fn);
+        assert_eq!(Token::Fn.as_keyword(), Some(Keyword::Fn));
+        assert_eq!(tokens[0].as_keyword(), Some(Keyword::Fn));
+        assert_eq!(Token::from(tokens[0].as_keyword().unwrap()), Lexer::new("fn").lex().unwrap()[0]);
+        assert_eq!(Token::Ident("fn".into()).as_keyword(), None);
+    }
+
+    #[test]
+    fn caret_and_caret_eq() {
+        assert_eq!(spellings("a ^ b"), ["a", "^", "b"]);
+        assert_eq!(spellings("a ^= b"), ["a", "^=", "b"]);
+        assert_eq!(Lexer::new("a^=b^c").lex().unwrap()[1], Token::Op(Op::CaretEq));
+        assert_eq!(Lexer::new("a^=b^c").lex().unwrap()[3], Token::Op(Op::Caret));
+    }
+
+    #[test]
+    fn ints_floats_and_ranges() {
+        let int = |digits| Token::IntLit { digits, suffix: None };
+        assert_eq!(Lexer::new("5").lex().unwrap(), [int("5"), Token::EOF]);
+        assert_eq!(Lexer::new("5.0").lex().unwrap(), [Token::FloatLit { digits: "5.0", suffix: None }, Token::EOF]);
+        assert_eq!(Lexer::new("0..10").lex().unwrap(), [int("0"), Token::Op(Op::DotDot), int("10"), Token::EOF]);
+    }
+
+    #[test]
+    fn exponents_in_every_form() {
+        for source in ["1e10", "6.022e23", "1.5E-9", "2e+3"] {
+            assert_eq!(Lexer::new(source).lex().unwrap(), [Token::FloatLit { digits: source, suffix: None }, Token::EOF]);
+        }
+        for source in ["1e", "1e+", "1.5E-"] {
+            let err = Lexer::new(source).lex().unwrap_err();
+            assert!(matches!(err.kind(), LexErrorKind::ExponentHasNoDigits), "{source}: {err}");
+        }
+    }
+
+    #[test]
+    fn arrow_does_not_shadow_minus_ops() {
+        assert_eq!(spellings("fn f() -> i32"), ["fn", "f", "(", ")", "->", "i32"]);
+        assert_eq!(spellings("a -= b--"), ["a", "-=", "b", "--"]);
+        assert_eq!(spellings("a->b - c"), ["a", "->", "b", "-", "c"]);
+    }
+
+    #[test]
+    fn control_flow_keywords_but_not_words_containing_them() {
+        let tokens = Lexer::new("while for return break continue match").lex().unwrap();
+        assert_eq!(
+            tokens,
+            [Token::While, Token::For, Token::Return, Token::Break, Token::Continue, Token::Match, Token::EOF]
+        );
+        for word in ["formatting", "whiles", "returned", "matches", "breakfast", "continue_"] {
+            assert_eq!(Lexer::new(word).lex().unwrap(), [Token::Ident(word.into()), Token::EOF]);
+        }
+    }
+
+    #[test]
+    fn true_and_false_are_bool_literals() {
+        assert_eq!(
+            Lexer::new("let x := true").lex().unwrap(),
+            [Token::Let, Token::Ident("x".into()), Token::Op(Op::ColonEq), Token::BoolLit(true), Token::EOF]
+        );
+        assert_eq!(Lexer::new("false").lex().unwrap(), [Token::BoolLit(false), Token::EOF]);
+        assert_eq!(Lexer::new("truth").lex().unwrap(), [Token::Ident("truth".into()), Token::EOF]);
+    }
+
+    #[test]
+    fn every_line_ending_counts_once() {
+        let source = "a\nb\r\nc\rd\r\n\re";
+        let spans = Lexer::new(source).lex_spanned().unwrap();
+        let lines: Vec<_> = spans.iter().map(|(_, span)| span.start_line).collect();
+        assert_eq!(lines, [1, 2, 3, 4, 6, 6]);
+        assert_eq!(spans[4].1.start_col, 1);
+        let source = "x\r\n\r\n  `";
+        let out = Lexer::new(source).lex().unwrap_err().render(source);
+        assert!(out.contains("--> 3:3\n"), "{out}");
+    }
+
+    #[test]
+    fn bitwise_assign_after_the_doubled_forms() {
+        assert_eq!(spellings("a &= b"), ["a", "&=", "b"]);
+        assert_eq!(spellings("a |= b"), ["a", "|=", "b"]);
+        assert_eq!(spellings("a&&=b||=c"), ["a", "&&", "=", "b", "||", "=", "c"]);
+    }
+
+    #[test]
+    fn power_ops_are_tried_longest_first() {
+        assert_eq!(spellings("2 ** 8"), ["2", "**", "8"]);
+        assert_eq!(spellings("x **= 2"), ["x", "**=", "2"]);
+        assert_eq!(spellings("x*=y*z"), ["x", "*=", "y", "*", "z"]);
+        assert_eq!(spellings("x***y"), ["x", "**", "*", "y"]);
+    }
+
+    #[test]
+    fn leading_bom_is_skipped_but_not_a_later_one() {
+        let spans = Lexer::new("\u{FEFF}fn f").lex_spanned().unwrap();
+        assert_eq!(spans[0], (Token::Fn, Span { start: 3, end: 5, start_line: 1, start_col: 1, end_line: 1, end_col: 3 }));
+        let err = Lexer::new("fn \u{FEFF}f").lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::IllegalUnicodeChar('\u{FEFF}')), "{err}");
+        assert_eq!(err.offset(), Some(3));
+    }
+
+    #[test]
+    fn limits_stop_long_sources_and_many_tokens() {
+        let limits = LexerLimits { max_bytes: 8, max_token_count: 3 };
+        let err = Lexer::new_with_limits("a b c d e", limits).err().unwrap();
+        assert!(matches!(err.kind(), LexErrorKind::SourceTooLong { len: 9, max: 8 }), "{err}");
+        let err = Lexer::new_with_limits("a b c d", limits).unwrap().lex().unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::TooManyTokens(3)), "{err}");
+        assert_eq!(err.offset(), Some(6));
+        assert_eq!(Lexer::new_with_limits("a b c", limits).unwrap().lex().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn only_three_slashes_make_a_doc_comment() {
+        let lex = |source| Lexer::new(source).lex().unwrap();
+        assert_eq!(lex("// plain\nx"), [Token::Ident("x".into()), Token::EOF]);
+        assert_eq!(lex("/// doc\nx")[0], Token::DocComment(DocStyle::Outer, DocForm::Line, " doc".into()));
+        assert_eq!(lex("//// plain\nx"), [Token::Ident("x".into()), Token::EOF]);
+        assert_eq!(lex("/** doc */")[0], Token::DocComment(DocStyle::Outer, DocForm::Block, " doc ".into()));
+        assert_eq!(lex("/*** plain */"), [Token::EOF]);
+    }
+
+    #[test]
+    fn blank_lines_collapse_to_one_newline() {
+        let tokens = Lexer::new("a\n\n  \r\n\tb\nc").significant_newlines(true).lex().unwrap();
+        let ident = |name: &'static str| Token::Ident(name.into());
+        assert_eq!(tokens, [ident("a"), Token::Newline, ident("b"), Token::Newline, ident("c"), Token::EOF]);
+        assert_eq!(Lexer::new("a\n\nb").lex().unwrap(), [ident("a"), ident("b"), Token::EOF]);
+    }
+
+    #[test]
+    fn line_continuations_but_not_escaped_backslashes() {
+        let lex = |source| Lexer::new(source).lex().unwrap();
+        assert_eq!(lex("\"foo\\\nbar\""), [Token::StrLit(b"foobar".to_vec()), Token::EOF]);
+        assert_eq!(lex("\"foo\\\r\nbar\""), [Token::StrLit(b"foobar".to_vec()), Token::EOF]);
+        assert_eq!(lex("\"a\\\\\n\""), [Token::StrLit(b"a\\\n".to_vec()), Token::EOF]);
+    }
+
+    #[test]
+    fn trivia_and_tokens_rebuild_the_source() {
+        let source = "  fn f(x)\t-> u8 {\r\n    x + 1\n}\n";
+        let tokens = Lexer::new(source).lex_with_trivia().unwrap();
+        let rebuilt: String = tokens
+            .iter()
+            .map(|t| match t.token {
+                Token::EOF => t.leading_trivia.to_string(),
+                _ => format!("{}{}", t.leading_trivia, t.token),
+            })
+            .collect();
+        assert_eq!(rebuilt, source);
+        assert_eq!(tokens[0].leading_trivia, "  ");
+        assert_eq!(tokens.last().unwrap().leading_trivia, "\n");
+    }
+
+    #[test]
+    fn token_text_recovers_the_source_spelling() {
+        let source = "name = \"a\\tb\"";
+        let lexer = &mut Lexer::new(source);
+        let spans = lexer.lex_spanned().unwrap();
+        assert_eq!(lexer.token_text(spans[0].1), Some("name"));
+        assert_eq!(spans[2].0, Token::StrLit(b"a\tb".to_vec()));
+        assert_eq!(lexer.token_text(spans[2].1), Some("\"a\\tb\""));
+        assert_eq!(lexer.token_bytes(spans[2].1), Some(&b"\"a\\tb\""[..]));
+        let past_end = Span { start: 10, end: 99, ..spans[0].1 };
+        assert_eq!(lexer.token_text(past_end), None);
+    }
+
+    #[test]
+    fn random_bytes_never_panic() {
+        const ALPHABET: &[&[u8]] = &[
+            b"r", b"#", b"#!", b"\"", b"'", b"b", b"\\", b"x", b"0", b"1", b"9", b"_", b".", b"e", b"/", b"*",
+            b"//", b"/*", b"*/", b"///", b"!", b"\n", b"\r", b" ", b"\t", b"\0", b"\xff", b"\xc3", b"\xa9",
+            b"\xef\xbb\xbf", b"a", b"(", b")", b"{", b"]", b"=", b">", b"<", b"-", b"&", b"|", b"u8", b"f32", b"0x",
+        ];
+        let mut seed: u64 = 0x5eed;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for _ in 0..20_000 {
+            let mut source = Vec::new();
+            for _ in 0..next() % 12 {
+                let n = next();
+                if n % 5 == 0 {
+                    source.push((n >> 8) as u8);
+                } else {
+                    source.extend_from_slice(ALPHABET[(n >> 8) as usize % ALPHABET.len()]);
+                }
+            }
+            let all_options = || {
+                Lexer::from_bytes(&source)
+                    .check_brackets(true)
+                    .placeholders(true)
+                    .lazy_strings(true)
+                    .strict_ops(true)
+                    .preserve_comments(true)
+                    .parse_numbers(true)
+                    .significant_newlines(true)
+                    .tab_width(3)
+            };
+            let _ = crate::tokenize(&source);
+            let _ = Lexer::from_bytes(&source).lex_spanned();
+            let _ = Lexer::from_bytes(&source).lex_diagnostics();
+            let _ = Lexer::from_bytes(&source).lex_borrowed();
+            let _ = Lexer::from_bytes(&source).lex_with_trivia();
+            #[cfg(feature = "std")]
+            let _ = Lexer::from_bytes(&source).lex_interned();
+            if let Ok(tokens) = all_options().lex() {
+                let _ = validate_literals(&tokens);
+                for token in &tokens {
+                    if let Token::LazyStrLit(lazy) = token {
+                        let _ = lazy.decode();
+                    }
+                }
+            }
+            let _ = all_options().lex_borrowed();
+            if let Ok(text) = from_utf8(&source) {
+                if let Err(err) = Lexer::new(text).lex() {
+                    let _ = err.render(text);
+                }
+            }
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod lexer;
-pub mod ast_parser;
\ No newline at end of file
+pub mod interner;
+pub mod token_stream;
+pub mod ast_parser;
+pub mod error;
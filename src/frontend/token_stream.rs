@@ -0,0 +1,124 @@
+use core::{error::Error, fmt::{Debug, Display}};
+use alloc::{format, string::String};
+
+use super::lexer::Token;
+
+/// A cursor over lexed tokens with any amount of lookahead. Reading past
+/// the end keeps yielding `EOF`.
+pub struct TokenStream<'a> {
+    src: &'a [Token<'a>],
+    /// Byte offset of each token in `src`, or empty if they aren't known.
+    offsets: &'a [usize],
+    i: usize,
+}
+impl<'a> TokenStream<'a> {
+    pub fn new(src: &'a [Token<'a>]) -> Self {
+        Self::with_offsets(src, &[])
+    }
+    /// A stream whose errors point at `offsets[i]` for the `i`th token.
+    pub fn with_offsets(src: &'a [Token<'a>], offsets: &'a [usize]) -> Self {
+        Self { src, offsets, i: 0 }
+    }
+
+    pub fn peek(&self) -> &'a Token<'a> {
+        self.peek_nth(0)
+    }
+    /// The token `n` places ahead, so `peek_nth(0)` is `peek()`.
+    pub fn peek_nth(&self, n: usize) -> &'a Token<'a> {
+        static EOF: Token = Token::EOF;
+        self.src.get(self.i + n).unwrap_or(&EOF)
+    }
+    /// Not `Iterator::next`: the stream never runs out, it stays on `EOF`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> &'a Token<'a> {
+        let ret = self.peek();
+        if self.i < self.src.len() {
+            self.i += 1;
+        }
+        ret
+    }
+    /// Byte offset of the next token, or of the last one once past the end.
+    pub fn offset(&self) -> Option<usize> {
+        self.offsets.get(self.i).or(self.offsets.last()).copied()
+    }
+    /// Byte offset of the token `next` returned last.
+    pub fn prev_offset(&self) -> Option<usize> {
+        self.offsets.get(self.i.saturating_sub(1)).copied()
+    }
+    /// Consumes the next token if it is `expected`.
+    pub fn eat(&mut self, expected: &Token) -> bool {
+        if self.peek() == expected {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+    pub fn expect(&mut self, expected: &Token) -> Result<&'a Token<'a>, UnexpectedToken> {
+        if self.peek() == expected {
+            Ok(self.next())
+        } else {
+            Err(UnexpectedToken {
+                message: format!("Expected {:?}, Found {:?}", expected, self.peek()),
+                offset: self.offset(),
+            })
+        }
+    }
+}
+
+pub struct UnexpectedToken {
+    message: String,
+    offset: Option<usize>,
+}
+impl UnexpectedToken {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// Byte offset of the token that wasn't expected, if the stream knows it.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+impl Error for UnexpectedToken {}
+impl Display for UnexpectedToken {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl Debug for UnexpectedToken {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "UnexpectedToken: {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Op;
+
+    #[test]
+    fn peeking_past_the_end_returns_eof() {
+        let tokens = [Token::Ident("x".into()), Token::Op(Op::Semi), Token::EOF];
+        let mut stream = TokenStream::new(&tokens);
+        assert_eq!(stream.peek_nth(1), &Token::Op(Op::Semi));
+        assert_eq!(stream.peek_nth(2), &Token::EOF);
+        assert_eq!(stream.peek_nth(100), &Token::EOF);
+        for _ in 0..5 {
+            stream.next();
+        }
+        assert_eq!(stream.peek(), &Token::EOF);
+        assert_eq!(stream.peek_nth(1), &Token::EOF);
+        assert_eq!(TokenStream::new(&[]).peek_nth(0), &Token::EOF);
+    }
+
+    #[test]
+    fn expect_reports_the_offset_of_the_unexpected_token() {
+        let tokens = [Token::Ident("x".into()), Token::Op(Op::Semi), Token::EOF];
+        let mut stream = TokenStream::with_offsets(&tokens, &[0, 2, 3]);
+        stream.next();
+        assert_eq!(stream.prev_offset(), Some(0));
+        let err = stream.expect(&Token::Op(Op::Comma)).unwrap_err();
+        assert_eq!(err.offset(), Some(2));
+        assert_eq!(TokenStream::new(&tokens).expect(&Token::EOF).unwrap_err().offset(), None);
+    }
+}
@@ -0,0 +1,22 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod frontend;
+
+pub use frontend::lexer;
+
+use alloc::vec::Vec;
+use lexer::{Lexer, LexingError, Token};
+
+/// Lexes a whole source file, as a `str` or as raw bytes. The source may or
+/// may not end in the `\0` sentinel. Never panics, whatever the input, so
+/// it doubles as the fuzz target.
+pub fn tokenize<S: AsRef<[u8]> + ?Sized>(source: &S) -> Result<Vec<Token<'_>>, LexingError> {
+    Lexer::from_bytes(source.as_ref()).lex()
+}
+
+/// Like `tokenize`, for raw file contents that may not be valid UTF-8.
+pub fn lex_bytes(source: &[u8]) -> Result<Vec<Token<'_>>, LexingError> {
+    Lexer::from_bytes(source).lex()
+}
@@ -1,19 +1,60 @@
-mod frontend;
-
 use std::error::Error;
-use std::{fs, env};
+use std::{fs, env, process};
 
-use frontend::lexer::*;
-// use frontend::ast_parser::*;
+// use lemon_pie::frontend::ast_parser::*;
 
-fn main() -> Result<(), Box<dyn Error>>g
+fn main() -> Result<(), Box<dyn Error>> {
     env::set_var("RUST_BACKTRACE", "1");
 
-    let mut source = fs::read_to_string("./src/test_files/test1.lp")?;
-    source.push('\0');
+    let (flags, paths): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let mut emit_json = false;
+    for flag in &flags {
+        match flag.as_str() {
+            "--emit-tokens=json" if cfg!(feature = "serde") => emit_json = true,
+            "--emit-tokens=json" => {
+                eprintln!("error: --emit-tokens=json needs lemon_pie built with the 'serde' feature");
+                process::exit(2);
+            }
+            _ => {
+                eprintln!("error: unknown flag '{flag}'");
+                process::exit(2);
+            }
+        }
+    }
+    let [path] = paths.as_slice() else {
+        eprintln!("usage: lemon_pie [--emit-tokens=json] <file>");
+        process::exit(2);
+    };
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: couldn't read '{path}': {err}");
+            process::exit(1);
+        }
+    };
 
-    let token_stream = Lexer::new(&source).lex()?;
-    println!("{:?}", token_stream);
+    let token_stream = match lemon_pie::tokenize(&source) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprint!("{}", err.render_in(path, &source));
+            process::exit(1);
+        }
+    };
+    if emit_json {
+        print_json(&token_stream)?;
+    } else {
+        println!("{:?}", token_stream);
+    }
 
     Ok(())
 }
+
+#[cfg(feature = "serde")]
+fn print_json(tokens: &[lemon_pie::lexer::Token]) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string(tokens)?);
+    Ok(())
+}
+#[cfg(not(feature = "serde"))]
+fn print_json(_: &[lemon_pie::lexer::Token]) -> Result<(), Box<dyn Error>> {
+    unreachable!("the flag is rejected without the 'serde' feature")
+}